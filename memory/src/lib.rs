@@ -1,3 +1,11 @@
+// length-prefix format shared by the host ( VmMemory ) and the guest ABI
+pub trait MemCodec: std::fmt::Debug {
+    fn header_size(&self) -> usize;
+    fn encode(&self, bytes: &[u8]) -> Vec<u8>;
+    fn decode_len(&self, len: &[u8]) -> usize;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Memory;
 impl Memory {
     #[unsafe(no_mangle)]
@@ -18,8 +26,8 @@ impl Memory {
     pub fn new(len: usize, data: &[u8]) -> *mut u8 {
         let mut buffer = Vec::with_capacity(4 + len);
 
-        // data_len ( 4byte ) + data
-        buffer.extend_from_slice(&len.to_le_bytes());
+        // data_len ( 4byte, le ) + data
+        buffer.extend_from_slice(&(len as u32).to_le_bytes());
         buffer.extend_from_slice(data);
 
         let ptr = buffer.as_mut_ptr();
@@ -42,7 +50,7 @@ impl Memory {
         let len_val = bytes.len() as u32;
         let mut buffer = Vec::with_capacity(4 + len_val as usize);
 
-        // len ( 4byte ) + val
+        // len ( 4byte, le ) + val
         buffer.extend_from_slice(&len_val.to_le_bytes());
         buffer.extend_from_slice(bytes);
 
@@ -80,3 +88,18 @@ impl Memory {
         input_len
     }
 }
+
+// default codec : len ( 4byte, le ) + data
+impl MemCodec for Memory {
+    fn header_size(&self) -> usize {
+        4
+    }
+
+    fn encode(&self, bytes: &[u8]) -> Vec<u8> {
+        Memory::encode(bytes)
+    }
+
+    fn decode_len(&self, len: &[u8]) -> usize {
+        Memory::decode_len(len)
+    }
+}
@@ -1,11 +1,13 @@
 use std::sync::Arc;
 pub use wasmer::wasmparser::Operator;
-use wasmer::{CompilerConfig, Cranelift, Instance, Store, StoreMut};
+use wasmer::{CompilerConfig, Cranelift, Instance, ModuleMiddleware, Store, StoreMut};
 use wasmer_middlewares::{
     Metering,
     metering::{MeteringPoints, get_remaining_points, set_remaining_points},
 };
 
+use crate::GasConsumptionFn;
+
 #[derive(Debug)]
 pub struct GasMetering;
 
@@ -14,6 +16,8 @@ impl GasMetering {
 
     pub fn create_cfg(
         gas_consumption: Option<Arc<dyn Fn(&Operator) -> u64 + Send + Sync + 'static>>,
+        canonicalize_nans: bool,
+        middlewares: Vec<Arc<dyn ModuleMiddleware>>,
     ) -> impl CompilerConfig {
         // Set gas limit to 0 for module replication
         // Once module creation is complete, gas is injected.
@@ -25,8 +29,13 @@ impl GasMetering {
         let metering = Arc::new(Metering::new(gas_limit, consumption_fn));
 
         // Set compiler config with the metering middleware
+        // Extra middlewares run after metering, so their injected code is not charged.
         let mut compiler_config = Cranelift::default();
+        compiler_config.canonicalize_nans(canonicalize_nans);
         compiler_config.push_middleware(metering);
+        for middleware in middlewares {
+            compiler_config.push_middleware(middleware);
+        }
 
         compiler_config
     }
@@ -40,6 +49,13 @@ impl GasMetering {
         gas_left
     }
 
+    pub fn is_metered(instance: &Instance) -> bool {
+        instance
+            .exports
+            .get_global("wasmer_metering_remaining_points")
+            .is_ok()
+    }
+
     pub fn gas_decrease(store: &mut StoreMut<'_>, instance: &Instance, gas_expected: u64) -> bool {
         let gas_left = GasMetering::get_left_store_mute(store, instance);
         if gas_left < gas_expected {
@@ -64,52 +80,223 @@ impl GasMetering {
         set_remaining_points(store, instance, u64_gas);
     }
 
+    pub fn default_schedule() -> GasConsumptionFn {
+        GasSchedule::default().into_consumption()
+    }
+
     fn set_default_consumption() -> Arc<dyn Fn(&Operator) -> u64 + Send + Sync + 'static> {
         Arc::new(move |operator: &Operator| -> u64 {
-            let gas_by_opcode = match operator {
-                Operator::BrTable { .. } => 120,
-                Operator::Return { .. } => 90,
-
-                Operator::Call { .. } => 90,
-                Operator::CallIndirect { .. } => 10000,
-
-                Operator::I32Const { .. } => 1,
-                Operator::I32Add { .. } => 45,
-                Operator::I32Sub { .. } => 45,
-                Operator::I32Mul { .. } => 45,
-                Operator::I32DivS { .. } => 36000,
-                Operator::I32DivU { .. } => 36000,
-                Operator::I32RemS { .. } => 36000,
-                Operator::I32RemU { .. } => 36000,
-                Operator::I32And { .. } => 45,
-                Operator::I32Or { .. } => 45,
-                Operator::I32Xor { .. } => 45,
-                Operator::I32Shl { .. } => 67,
-                Operator::I32ShrU { .. } => 67,
-                Operator::I32ShrS { .. } => 67,
-                Operator::I32Rotl { .. } => 90,
-                Operator::I32Rotr { .. } => 90,
-                Operator::I32Eq { .. } => 45,
-                Operator::I32Eqz { .. } => 45,
-                Operator::I32Ne { .. } => 45,
-                Operator::I32LtS { .. } => 45,
-                Operator::I32LtU { .. } => 45,
-                Operator::I32LeS { .. } => 45,
-                Operator::I32LeU { .. } => 45,
-                Operator::I32GtS { .. } => 45,
-                Operator::I32GtU { .. } => 45,
-                Operator::I32GeS { .. } => 45,
-                Operator::I32GeU { .. } => 45,
-                Operator::I32Clz { .. } => 45,
-                Operator::I32Ctz { .. } => 45,
-                Operator::I32Popcnt { .. } => 45,
-
-                Operator::Drop { .. } => 120,
-                Operator::Select { .. } => 120,
-                Operator::Unreachable { .. } => 1,
-                _ => 1,
-            };
-            gas_by_opcode * Self::DEF_GAS_PRIORITY
+            Self::default_opcode_cost(operator) * Self::DEF_GAS_PRIORITY
         })
     }
+
+    fn default_opcode_cost(operator: &Operator) -> u64 {
+        match operator {
+            Operator::BrTable { .. } => 120,
+            Operator::Return { .. } => 90,
+
+            Operator::Call { .. } => 90,
+            Operator::CallIndirect { .. } => 10000,
+
+            Operator::I32Const { .. } => 1,
+            Operator::I32Add { .. } => 45,
+            Operator::I32Sub { .. } => 45,
+            Operator::I32Mul { .. } => 45,
+            Operator::I32DivS { .. } => 36000,
+            Operator::I32DivU { .. } => 36000,
+            Operator::I32RemS { .. } => 36000,
+            Operator::I32RemU { .. } => 36000,
+            Operator::I32And { .. } => 45,
+            Operator::I32Or { .. } => 45,
+            Operator::I32Xor { .. } => 45,
+            Operator::I32Shl { .. } => 67,
+            Operator::I32ShrU { .. } => 67,
+            Operator::I32ShrS { .. } => 67,
+            Operator::I32Rotl { .. } => 90,
+            Operator::I32Rotr { .. } => 90,
+            Operator::I32Eq { .. } => 45,
+            Operator::I32Eqz { .. } => 45,
+            Operator::I32Ne { .. } => 45,
+            Operator::I32LtS { .. } => 45,
+            Operator::I32LtU { .. } => 45,
+            Operator::I32LeS { .. } => 45,
+            Operator::I32LeU { .. } => 45,
+            Operator::I32GtS { .. } => 45,
+            Operator::I32GtU { .. } => 45,
+            Operator::I32GeS { .. } => 45,
+            Operator::I32GeU { .. } => 45,
+            Operator::I32Clz { .. } => 45,
+            Operator::I32Ctz { .. } => 45,
+            Operator::I32Popcnt { .. } => 45,
+
+            Operator::Drop { .. } => 120,
+            Operator::Select { .. } => 120,
+            Operator::Unreachable { .. } => 1,
+            _ => 1,
+        }
+    }
+}
+
+// Built-in schedule for operators the integer table leaves at the fallback cost.
+// Every field can be overridden before converting into a `GasConsumptionFn`:
+//
+//   let schedule = GasSchedule { float: 200, ..GasSchedule::default() };
+//   GasMetering::create_cfg(Some(schedule.into_consumption()), true, vec![]);
+//
+// ( gas_consumption, canonicalize_nans, extra middlewares run after metering )
+#[derive(Debug, Clone, PartialEq)]
+pub struct GasSchedule {
+    pub memory_access: u64, // load / store / memory.size
+    pub memory_grow: u64,   // memory.grow ( per call, not per page )
+    pub memory_bulk: u64,   // memory.copy / fill / init, data.drop
+    pub table: u64,         // table.get / set / size / grow / fill / copy / init, elem.drop
+    pub global: u64,        // global.get / global.set
+    pub float: u64,         // f32 / f64 arithmetic, compare & convert
+    pub float_div: u64,     // f32 / f64 div & sqrt
+    pub priority: u64,      // multiplier applied to every cost
+}
+
+impl Default for GasSchedule {
+    fn default() -> Self {
+        GasSchedule {
+            memory_access: 90,
+            memory_grow: 10000,
+            memory_bulk: 1000,
+            table: 120,
+            global: 45,
+            float: 90,
+            float_div: 36000,
+            priority: GasMetering::DEF_GAS_PRIORITY,
+        }
+    }
+}
+
+impl GasSchedule {
+    pub fn into_consumption(self) -> GasConsumptionFn {
+        Arc::new(move |operator: &Operator| -> u64 { self.cost(operator) * self.priority })
+    }
+
+    pub fn cost(&self, operator: &Operator) -> u64 {
+        match operator {
+            // memory
+            Operator::I32Load { .. }
+            | Operator::I64Load { .. }
+            | Operator::F32Load { .. }
+            | Operator::F64Load { .. }
+            | Operator::I32Load8S { .. }
+            | Operator::I32Load8U { .. }
+            | Operator::I32Load16S { .. }
+            | Operator::I32Load16U { .. }
+            | Operator::I64Load8S { .. }
+            | Operator::I64Load8U { .. }
+            | Operator::I64Load16S { .. }
+            | Operator::I64Load16U { .. }
+            | Operator::I64Load32S { .. }
+            | Operator::I64Load32U { .. }
+            | Operator::I32Store { .. }
+            | Operator::I64Store { .. }
+            | Operator::F32Store { .. }
+            | Operator::F64Store { .. }
+            | Operator::I32Store8 { .. }
+            | Operator::I32Store16 { .. }
+            | Operator::I64Store8 { .. }
+            | Operator::I64Store16 { .. }
+            | Operator::I64Store32 { .. }
+            | Operator::MemorySize { .. } => self.memory_access,
+            Operator::MemoryGrow { .. } => self.memory_grow,
+            Operator::MemoryInit { .. }
+            | Operator::DataDrop { .. }
+            | Operator::MemoryCopy { .. }
+            | Operator::MemoryFill { .. } => self.memory_bulk,
+
+            // table
+            Operator::TableInit { .. }
+            | Operator::ElemDrop { .. }
+            | Operator::TableCopy { .. }
+            | Operator::TableFill { .. }
+            | Operator::TableGet { .. }
+            | Operator::TableSet { .. }
+            | Operator::TableGrow { .. }
+            | Operator::TableSize { .. } => self.table,
+
+            // global
+            Operator::GlobalGet { .. } | Operator::GlobalSet { .. } => self.global,
+
+            // float
+            Operator::F32Div | Operator::F64Div | Operator::F32Sqrt | Operator::F64Sqrt => {
+                self.float_div
+            }
+            Operator::F32Const { .. }
+            | Operator::F64Const { .. }
+            | Operator::F32Eq
+            | Operator::F32Ne
+            | Operator::F32Lt
+            | Operator::F32Gt
+            | Operator::F32Le
+            | Operator::F32Ge
+            | Operator::F64Eq
+            | Operator::F64Ne
+            | Operator::F64Lt
+            | Operator::F64Gt
+            | Operator::F64Le
+            | Operator::F64Ge
+            | Operator::F32Abs
+            | Operator::F32Neg
+            | Operator::F32Ceil
+            | Operator::F32Floor
+            | Operator::F32Trunc
+            | Operator::F32Nearest
+            | Operator::F32Add
+            | Operator::F32Sub
+            | Operator::F32Mul
+            | Operator::F32Min
+            | Operator::F32Max
+            | Operator::F32Copysign
+            | Operator::F64Abs
+            | Operator::F64Neg
+            | Operator::F64Ceil
+            | Operator::F64Floor
+            | Operator::F64Trunc
+            | Operator::F64Nearest
+            | Operator::F64Add
+            | Operator::F64Sub
+            | Operator::F64Mul
+            | Operator::F64Min
+            | Operator::F64Max
+            | Operator::F64Copysign
+            | Operator::I32TruncF32S
+            | Operator::I32TruncF32U
+            | Operator::I32TruncF64S
+            | Operator::I32TruncF64U
+            | Operator::I64TruncF32S
+            | Operator::I64TruncF32U
+            | Operator::I64TruncF64S
+            | Operator::I64TruncF64U
+            | Operator::F32ConvertI32S
+            | Operator::F32ConvertI32U
+            | Operator::F32ConvertI64S
+            | Operator::F32ConvertI64U
+            | Operator::F32DemoteF64
+            | Operator::F64ConvertI32S
+            | Operator::F64ConvertI32U
+            | Operator::F64ConvertI64S
+            | Operator::F64ConvertI64U
+            | Operator::F64PromoteF32
+            | Operator::I32ReinterpretF32
+            | Operator::I64ReinterpretF64
+            | Operator::F32ReinterpretI32
+            | Operator::F64ReinterpretI64
+            | Operator::I32TruncSatF32S
+            | Operator::I32TruncSatF32U
+            | Operator::I32TruncSatF64S
+            | Operator::I32TruncSatF64U
+            | Operator::I64TruncSatF32S
+            | Operator::I64TruncSatF32U
+            | Operator::I64TruncSatF64S
+            | Operator::I64TruncSatF64U => self.float,
+
+            // integer & control ( same as the built-in table )
+            _ => GasMetering::default_opcode_cost(operator),
+        }
+    }
 }
@@ -1,18 +1,57 @@
-use std::{collections::HashMap, marker::PhantomData};
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    marker::PhantomData,
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+};
 
 use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
 use wasmer::{
-    Exports, Function, FunctionEnv, FunctionEnvMut, FunctionType, Imports, Instance, Module,
-    RuntimeError, Store, Value,
+    Exports, Function, FunctionEnv, FunctionEnvMut, FunctionType, Imports, Instance,
+    InstantiationError, LinkError, Module, RuntimeError, Store, Type, Value,
 };
+use wasmer_types::ImportError;
 
+use crate::core::interrupt::*;
 use crate::data::*;
 
-#[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize, Clone)]
+#[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
 pub enum InstanceError {
     NewInstanceCreateFail(String),
+    LinkImportUnknown(String),      // "module.name"
+    LinkImportIncompatible(String), // "module.name"
+    LinkMemoryFail(String),
+    StartTrap(String),
+}
+
+// trap payload for a panic caught inside a host fn ( never unwinds into wasm )
+#[derive(Debug)]
+pub struct HostPanic(pub String);
+
+impl HostPanic {
+    fn from_payload(payload: Box<dyn Any + Send>) -> Self {
+        let message = match payload.downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => match payload.downcast_ref::<String>() {
+                Some(message) => message.clone(),
+                None => "unknown panic".to_string(),
+            },
+        };
+        HostPanic(message)
+    }
 }
 
+impl fmt::Display for HostPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "host fn panic : {}", self.0)
+    }
+}
+
+impl std::error::Error for HostPanic {}
+
 pub struct VmInstance<T: Send + Sync + Clone + 'static> {
     _marker: PhantomData<T>,
 }
@@ -24,6 +63,51 @@ pub type ImportedFn<T> = Box<
         + 'static,
 >;
 
+// kept by the launcher, so `reset` can rebuild the imports into a fresh store
+pub type SharedImportedFn<T> = Arc<
+    dyn (Fn(FunctionEnvMut<'_, (VmData, Option<T>)>, &[Value]) -> Result<Vec<Value>, RuntimeError>)
+        + Send
+        + Sync
+        + 'static,
+>;
+
+pub type SharedImports<T> = BTreeMap<String, (SharedImportedFn<T>, FunctionType)>;
+
+// host fn (i32 ptr) -> i32 ptr, input read & result written with the VmData codec / allocator
+// ( re-entering the guest through `VmData::call_export` )
+pub fn host_fn_bytes<T, H>(name: &str, handler: H) -> (String, (ImportedFn<T>, FunctionType))
+where
+    T: Send + Sync + Clone + 'static,
+    H: Fn(&mut FunctionEnvMut<'_, (VmData, Option<T>)>, &[u8]) -> Vec<u8> + Send + Sync + 'static,
+{
+    let host_fn: ImportedFn<T> = Box::new(move |mut env, args| {
+        // load - input ptr
+        let ptr = match args.first() {
+            Some(Value::I32(ptr)) => *ptr,
+            _ => return Err(RuntimeError::new("host fn bytes : i32 ptr arg expected")),
+        };
+
+        // read - input
+        let (data, mut store) = env.data_and_store_mut();
+        let input = data
+            .0
+            .memory_read(&mut store, vec![ptr])
+            .map_err(|e| RuntimeError::new(format!("{:?}", e)))?
+            .remove(0);
+
+        // call - handler
+        let output = handler(&mut env, &input);
+
+        // write - output ( the allocator call counts toward the call depth )
+        let ptr = VmData::memory_write_export(&mut env, &output)?;
+
+        Ok(vec![Value::I32(ptr as i32)])
+    });
+    let fn_type = FunctionType::new(vec![Type::I32], vec![Type::I32]);
+
+    (name.to_string(), (host_fn, fn_type))
+}
+
 impl<T: Send + Sync + Clone + 'static> VmInstance<T> {
     pub fn new<F>(
         store: &mut Store,
@@ -41,27 +125,109 @@ impl<T: Send + Sync + Clone + 'static> VmInstance<T> {
             + Sync
             + 'static,
     {
+        let (import_obj, vm_env) = VmInstance::imports(store, vm_data, external, imported_fn);
+        let instance = VmInstance::instantiate(store, module, &import_obj, &vm_env)?;
+
+        Ok(Some(instance))
+    }
+
+    pub fn imports<F>(
+        store: &mut Store,
+        vm_data: VmData,
+        external: Option<T>,
+        imported_fn: HashMap<String, (F, FunctionType)>,
+    ) -> (Imports, FunctionEnv<(VmData, Option<T>)>)
+    where
+        F: Fn(
+                FunctionEnvMut<'_, (VmData, Option<T>)>,
+                &[Value],
+            ) -> Result<Vec<Value>, RuntimeError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let host_fns = Self::share(imported_fn);
+        Self::imports_shared(store, vm_data, external, &host_fns)
+    }
+
+    // share - host fns ( sorted by name, independent of HashMap order )
+    pub fn share<F>(imported_fn: HashMap<String, (F, FunctionType)>) -> SharedImports<T>
+    where
+        F: Fn(
+                FunctionEnvMut<'_, (VmData, Option<T>)>,
+                &[Value],
+            ) -> Result<Vec<Value>, RuntimeError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        imported_fn
+            .into_iter()
+            .map(|(fn_name, (fn_instance, fn_type))| {
+                let fn_instance: SharedImportedFn<T> = Arc::new(fn_instance);
+                (fn_name, (fn_instance, fn_type))
+            })
+            .collect()
+    }
+
+    pub fn imports_shared(
+        store: &mut Store,
+        vm_data: VmData,
+        external: Option<T>,
+        host_fns: &SharedImports<T>,
+    ) -> (Imports, FunctionEnv<(VmData, Option<T>)>) {
         // init - imports & env
         let mut import_obj = Imports::new();
         let mut vm_env_imports = Exports::new();
         let vm_env = FunctionEnv::new(store, (vm_data, external));
 
         // set - imports & env
-        for (fn_name, (fn_instance, fn_type)) in imported_fn {
-            vm_env_imports.insert(
-                fn_name,
-                Function::new_with_env(store, &vm_env, fn_type, fn_instance),
-            );
+        for (fn_name, (fn_instance, fn_type)) in host_fns {
+            // wrap - host fn panics become traps
+            let fn_instance = fn_instance.clone();
+            let function =
+                Function::new_with_env(store, &vm_env, fn_type.clone(), move |env, args| {
+                    panic::catch_unwind(AssertUnwindSafe(|| fn_instance(env, args))).unwrap_or_else(
+                        |payload| {
+                            Err(RuntimeError::user(Box::new(HostPanic::from_payload(
+                                payload,
+                            ))))
+                        },
+                    )
+                });
+            vm_env_imports.insert(fn_name.clone(), function);
         }
 
         import_obj.register_namespace("env", vm_env_imports);
 
+        // set - Interrupt middleware check fn ( unused without the middleware )
+        let check = Function::new_typed_with_env(
+            store,
+            &vm_env,
+            |env: FunctionEnvMut<'_, (VmData, Option<T>)>| match env.data().0.deadline_passed() {
+                true => Err(RuntimeError::new("deadline exceeded")),
+                false => Ok(()),
+            },
+        );
+        let mut interrupt_imports = Exports::new();
+        interrupt_imports.insert(Interrupt::IMPORT_CHECK, check);
+        import_obj.register_namespace(Interrupt::IMPORT_MODULE, interrupt_imports);
+
+        (import_obj, vm_env)
+    }
+
+    pub fn instantiate(
+        store: &mut Store,
+        module: &Module,
+        import_obj: &Imports,
+        vm_env: &FunctionEnv<(VmData, Option<T>)>,
+    ) -> Result<Instance, InstanceError> {
         // new - instance
-        let instance = Instance::new(store, module, &import_obj)
-            .map_err(|e| InstanceError::NewInstanceCreateFail(e.to_string()))?;
+        let instance =
+            Instance::new(store, module, import_obj).map_err(Self::instantiation_error)?;
 
         // load - env mut
-        let mut vm_env_mut = vm_env.into_mut(store);
+        let mut vm_env_mut = vm_env.clone().into_mut(store);
         let (vm_data, _opt_external) = vm_env_mut.data_mut();
 
         // save - instance & memory
@@ -69,6 +235,27 @@ impl<T: Send + Sync + Clone + 'static> VmInstance<T> {
         let memory = instance.exports.get_memory("memory").unwrap();
         vm_data.memory_set(memory);
 
-        Ok(Some(instance))
+        Ok(instance)
+    }
+
+    fn instantiation_error(e: InstantiationError) -> InstanceError {
+        match e {
+            InstantiationError::Link(LinkError::Import(
+                module,
+                name,
+                ImportError::UnknownImport(_),
+            )) => InstanceError::LinkImportUnknown(format!("{}.{}", module, name)),
+            InstantiationError::Link(LinkError::Import(
+                module,
+                name,
+                ImportError::IncompatibleType(..),
+            )) => InstanceError::LinkImportIncompatible(format!("{}.{}", module, name)),
+            InstantiationError::Link(LinkError::Import(_, _, ImportError::MemoryError(e))) => {
+                InstanceError::LinkMemoryFail(e)
+            }
+            InstantiationError::Link(LinkError::Resource(e)) => InstanceError::LinkMemoryFail(e),
+            InstantiationError::Start(e) => InstanceError::StartTrap(e.message()),
+            e => InstanceError::NewInstanceCreateFail(e.to_string()),
+        }
     }
 }
@@ -0,0 +1,209 @@
+use std::sync::Mutex;
+
+use wasmer::wasmparser::{BlockType, Operator};
+use wasmer::{
+    FunctionMiddleware, FunctionType, GlobalInit, GlobalType, LocalFunctionIndex, MiddlewareError,
+    MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+};
+use wasmer_types::entity::{EntityRef, PrimaryMap};
+use wasmer_types::{
+    ExportIndex, FunctionIndex, GlobalIndex, ImportIndex, ImportKey, ModuleInfo, SignatureIndex,
+};
+
+// Lets the host stop a running guest. Every function entry and loop header
+// counts down a global, and every `CHECK_INTERVAL` checkpoints the guest calls
+// the imported `wasmer_interrupt.check` host fn, which traps once the run's
+// deadline has passed. The import is added to the module here, so every
+// function index from the old import count on shifts by one and the
+// instructions & initializers referring to them are remapped.
+//
+// Like `Metering`, one instance instruments one module at a time.
+#[derive(Debug, Default)]
+pub struct Interrupt {
+    indexes: Mutex<Option<InterruptIndexes>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct InterruptIndexes {
+    counter: GlobalIndex,
+    check: FunctionIndex, // also the first shifted index
+}
+
+impl Interrupt {
+    pub const IMPORT_MODULE: &'static str = "wasmer_interrupt";
+    pub const IMPORT_CHECK: &'static str = "check";
+    pub const CHECK_INTERVAL: i32 = 1024;
+
+    pub fn new() -> Self {
+        Interrupt::default()
+    }
+
+    // () -> () signature of the check host fn
+    pub fn check_type() -> FunctionType {
+        FunctionType::new(vec![], vec![])
+    }
+}
+
+impl ModuleMiddleware for Interrupt {
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        let indexes = self.indexes.lock().unwrap().unwrap();
+
+        Box::new(FunctionInterrupt {
+            counter: indexes.counter.as_u32(),
+            check: indexes.check.as_u32(),
+            entered: false,
+        })
+    }
+
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) -> Result<(), MiddlewareError> {
+        // add - check fn import ( after the imported fns, the local fns shift by one )
+        let check = FunctionIndex::new(module_info.num_imported_functions);
+        let signature: SignatureIndex = module_info.signatures.push(Self::check_type());
+        let mut functions: Vec<SignatureIndex> = module_info.functions.values().copied().collect();
+        functions.insert(check.index(), signature);
+        module_info.functions = functions.into_iter().collect::<PrimaryMap<_, _>>();
+        module_info.num_imported_functions += 1;
+
+        // shift - fn indexes referenced by the module
+        let shift = |index: &mut FunctionIndex| *index = shift_index(*index, check);
+        for export in module_info.exports.values_mut() {
+            if let ExportIndex::Function(index) = export {
+                shift(index);
+            }
+        }
+        if let Some(index) = module_info.start_function.as_mut() {
+            shift(index);
+        }
+        for initializer in module_info.table_initializers.iter_mut() {
+            initializer.elements.iter_mut().for_each(shift);
+        }
+        for elements in module_info.passive_elements.values_mut() {
+            elements.iter_mut().for_each(shift);
+        }
+        for initializer in module_info.global_initializers.values_mut() {
+            if let GlobalInit::RefFunc(index) = initializer {
+                shift(index);
+            }
+        }
+        module_info.function_names = module_info
+            .function_names
+            .drain()
+            .map(|(index, name)| (shift_index(index, check), name))
+            .collect();
+
+        // add - import entry ( last one, `import_idx` is the position in `imports` )
+        let import_idx = module_info.imports.len() as u32;
+        module_info.imports.insert(
+            ImportKey {
+                module: Self::IMPORT_MODULE.to_string(),
+                field: Self::IMPORT_CHECK.to_string(),
+                import_idx,
+            },
+            ImportIndex::Function(check),
+        );
+
+        // add - checkpoint counter global ( not exported )
+        let counter = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+
+        *self.indexes.lock().unwrap() = Some(InterruptIndexes { counter, check });
+        Ok(())
+    }
+}
+
+// index after inserting the check fn at `check` ( null elements stay null )
+fn shift_index(index: FunctionIndex, check: FunctionIndex) -> FunctionIndex {
+    match index.as_u32() {
+        u32::MAX => index,
+        x if x >= check.as_u32() => FunctionIndex::from_u32(x + 1),
+        _ => index,
+    }
+}
+
+#[derive(Debug)]
+struct FunctionInterrupt {
+    counter: u32,
+    check: u32,
+    entered: bool,
+}
+
+impl FunctionInterrupt {
+    fn checkpoint<'a>(&self, state: &mut MiddlewareReaderState<'a>) {
+        // count down, call the check fn & restart the count at 0
+        state.extend(&[
+            Operator::GlobalGet {
+                global_index: self.counter,
+            },
+            Operator::If {
+                blockty: BlockType::Empty,
+            },
+            Operator::GlobalGet {
+                global_index: self.counter,
+            },
+            Operator::I32Const { value: 1 },
+            Operator::I32Sub,
+            Operator::GlobalSet {
+                global_index: self.counter,
+            },
+            Operator::Else,
+            Operator::I32Const {
+                value: Interrupt::CHECK_INTERVAL,
+            },
+            Operator::GlobalSet {
+                global_index: self.counter,
+            },
+            Operator::Call {
+                function_index: self.check,
+            },
+            Operator::End,
+        ]);
+    }
+
+    fn shift(&self, index: u32) -> u32 {
+        shift_index(
+            FunctionIndex::from_u32(index),
+            FunctionIndex::from_u32(self.check),
+        )
+        .as_u32()
+    }
+}
+
+impl FunctionMiddleware for FunctionInterrupt {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        // check - function entry
+        if !self.entered {
+            self.entered = true;
+            self.checkpoint(state);
+        }
+
+        match operator {
+            // check - loop header ( every iteration branches back here )
+            Operator::Loop { .. } => {
+                state.push_operator(operator);
+                self.checkpoint(state);
+            }
+
+            // shift - fn indexes past the inserted import
+            Operator::Call { function_index } => state.push_operator(Operator::Call {
+                function_index: self.shift(function_index),
+            }),
+            Operator::ReturnCall { function_index } => state.push_operator(Operator::ReturnCall {
+                function_index: self.shift(function_index),
+            }),
+            Operator::RefFunc { function_index } => state.push_operator(Operator::RefFunc {
+                function_index: self.shift(function_index),
+            }),
+            _ => state.push_operator(operator),
+        }
+
+        Ok(())
+    }
+}
@@ -1,3 +1,6 @@
 pub mod gas;
 pub mod instance;
-pub mod module;
\ No newline at end of file
+pub mod interrupt;
+pub mod module;
+pub mod peak;
+pub mod stack;
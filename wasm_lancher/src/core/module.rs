@@ -1,7 +1,17 @@
+use std::collections::BTreeSet;
+
 use borsh::{BorshDeserialize, BorshSerialize};
-use wasmer::{Module, Store};
+use serde::{Deserialize, Serialize};
+use wasmer::{
+    Engine, ExportIndex, Module, Store,
+    wasmparser::{
+        BlockType, MemoryType, Operator, Parser, Payload, TypeRef, ValType, Validator, WasmFeatures,
+    },
+    wat2wasm,
+};
+use wasmer_types::ModuleHash;
 
-#[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize, Clone)]
+#[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
 pub enum ModuleError {
     InitByWasmBinaryFail(String),
     InitByEncodedModuleFail(String),
@@ -10,19 +20,173 @@ pub enum ModuleError {
 
     ExportVecModuleEmpty,
     ExportVecModuleSerializeFail(String),
+    EncodedModuleChecksumInvalid,
+
+    ValidateFail(String),
+    FeatureNotAllowed(WasmFeature, String),
+    StartFunctionNotAllowed,
+    FloatOpForbidden(String),
+    LimitExceeded {
+        limit: ModuleLimit,
+        max: u64,
+        got: u64,
+    },
+}
+
+#[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+pub enum WasmFeature {
+    Floats,
+    Simd,
+    Threads,
+    BulkMemory,
+}
+
+impl WasmFeature {
+    pub fn to_flag(&self) -> WasmFeatures {
+        match self {
+            WasmFeature::Floats => WasmFeatures::FLOATS,
+            WasmFeature::Simd => WasmFeatures::SIMD | WasmFeatures::RELAXED_SIMD,
+            WasmFeature::Threads => WasmFeatures::THREADS | WasmFeatures::SHARED_EVERYTHING_THREADS,
+            WasmFeature::BulkMemory => WasmFeatures::BULK_MEMORY,
+        }
+    }
+}
+
+// true = allowed
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeaturePolicy {
+    pub floats: bool,
+    pub simd: bool,
+    pub threads: bool,
+    pub bulk_memory: bool,
+}
+
+impl FeaturePolicy {
+    pub fn allow_all() -> Self {
+        FeaturePolicy {
+            floats: true,
+            simd: true,
+            threads: true,
+            bulk_memory: true,
+        }
+    }
+
+    pub fn deterministic() -> Self {
+        FeaturePolicy {
+            floats: false,
+            simd: false,
+            threads: false,
+            bulk_memory: false,
+        }
+    }
+
+    pub fn disallowed(&self) -> Vec<WasmFeature> {
+        let mut features = vec![];
+        if !self.floats {
+            features.push(WasmFeature::Floats);
+        }
+        if !self.simd {
+            features.push(WasmFeature::Simd);
+        }
+        if !self.threads {
+            features.push(WasmFeature::Threads);
+        }
+        if !self.bulk_memory {
+            features.push(WasmFeature::BulkMemory);
+        }
+
+        features
+    }
+}
+
+#[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+pub enum ModuleLimit {
+    Imports,
+    Exports,
+    Functions,
+    MemoryPages,
+    Globals,
+}
+
+// counts include imported functions / globals / memories
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModulePolicy {
+    pub max_imports: u64,
+    pub max_exports: u64,
+    pub max_functions: u64,
+    pub max_memory_pages_declared: u64, // max( initial, maximum ) of any memory
+    pub max_globals: u64,
+
+    // reject any f32 / f64 operator ( incl. conversions ), stricter than nan canonicalization
+    pub forbid_float_ops: bool,
+
+    // proposals the guest may use, checked with validate_features
+    pub features: FeaturePolicy,
+}
+
+impl Default for ModulePolicy {
+    fn default() -> Self {
+        ModulePolicy {
+            max_imports: 1_000,
+            max_exports: 1_000,
+            max_functions: 100_000,
+            max_memory_pages_declared: 4_096, // 256 MiB
+            max_globals: 1_000,
+            forbid_float_ops: false,
+            features: FeaturePolicy::allow_all(),
+        }
+    }
 }
 
-#[derive(Debug)]
+impl ModulePolicy {
+    pub fn check(&self, limit: ModuleLimit, got: u64) -> Result<(), ModuleError> {
+        let max = match limit {
+            ModuleLimit::Imports => self.max_imports,
+            ModuleLimit::Exports => self.max_exports,
+            ModuleLimit::Functions => self.max_functions,
+            ModuleLimit::MemoryPages => self.max_memory_pages_declared,
+            ModuleLimit::Globals => self.max_globals,
+        };
+        if got > max {
+            return Err(ModuleError::LimitExceeded { limit, max, got });
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct VmModule {
     op_module: Option<Module>,
 }
 
 impl VmModule {
+    // wasmer 5 serialized artifact header
+    pub const SERIALIZED_MODULE_MAGIC: &'static [u8] = b"wasmer-universal";
+    pub const WASM_MAGIC: &'static [u8] = b"\0asm";
+    // [ magic ][ sha256 of the artifact ][ serialized artifact ] ( `seal_module_opcode` )
+    pub const SEALED_MODULE_MAGIC: &'static [u8] = b"lancher-sealed";
+
     pub fn new() -> Self {
         VmModule { op_module: None }
     }
 
     pub fn import(&mut self, store: &Store, wasm_binary: &[u8]) -> Result<(), ModuleError> {
+        self.import_with_policy(store, wasm_binary, &ModulePolicy::default())
+    }
+
+    pub fn import_with_policy(
+        &mut self,
+        store: &Store,
+        wasm_binary: &[u8],
+        policy: &ModulePolicy,
+    ) -> Result<(), ModuleError> {
+        // check - module limits & wasm features ( before compiling )
+        VmModule::check_policy(wasm_binary, policy)?;
+        if !policy.features.disallowed().is_empty() {
+            VmModule::validate_features(wasm_binary, policy.features.clone())?;
+        }
+
         // new - module
         let module = Module::new(store, wasm_binary)
             .map_err(|e| ModuleError::InitByWasmBinaryFail(e.to_string()))?;
@@ -37,6 +201,21 @@ impl VmModule {
         mut store: &Store,
         encoded_module: &[u8],
     ) -> Result<(), ModuleError> {
+        // check - header ( raw wasm passed with opcode_module_used is a common mistake )
+        if encoded_module.starts_with(Self::WASM_MAGIC) {
+            return Err(ModuleError::InitByEncodedModuleFail(
+                "expected serialized module, got raw wasm".to_string(),
+            ));
+        }
+
+        // check - checksum of a sealed module ( deserialize checks the layout, not the code )
+        let encoded_module = Self::unseal_module_opcode(encoded_module)?;
+        if !encoded_module.starts_with(Self::SERIALIZED_MODULE_MAGIC) {
+            return Err(ModuleError::InitByEncodedModuleFail(
+                "expected serialized module, header missing".to_string(),
+            ));
+        }
+
         // deserialize - encoded module
         let module = unsafe { Module::deserialize(&mut store, encoded_module) }
             .map_err(|e| ModuleError::InitByEncodedModuleFail(e.to_string()))?;
@@ -46,6 +225,422 @@ impl VmModule {
         Ok(())
     }
 
+    pub fn verify_module_opcode(engine: &Engine, encoded_module: &[u8]) -> Result<(), ModuleError> {
+        // import - into a throwaway store on the same engine
+        let store = Store::new(engine.clone());
+        let mut vm_module = VmModule::new();
+        vm_module.import_module_opcode(&store, encoded_module)
+    }
+
+    pub fn validate_features(opcode: &[u8], policy: FeaturePolicy) -> Result<(), ModuleError> {
+        let opcode = wat2wasm(opcode).map_err(|e| ModuleError::ValidateFail(e.to_string()))?;
+
+        // validate - default features plus every feature the policy covers ( catches malformed
+        // wasm, so a failure below is the disabled feature )
+        let features = [
+            WasmFeature::Floats,
+            WasmFeature::Simd,
+            WasmFeature::Threads,
+            WasmFeature::BulkMemory,
+        ]
+        .iter()
+        .fold(WasmFeatures::default(), |features, feature| {
+            features | feature.to_flag()
+        });
+        Validator::new_with_features(features)
+            .validate_all(&opcode)
+            .map_err(|e| ModuleError::ValidateFail(e.to_string()))?;
+
+        // validate - once per disallowed feature, with only that feature disabled
+        for feature in policy.disallowed() {
+            Validator::new_with_features(features - feature.to_flag())
+                .validate_all(&opcode)
+                .map_err(|e| ModuleError::FeatureNotAllowed(feature, e.to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn check_policy(opcode: &[u8], policy: &ModulePolicy) -> Result<(), ModuleError> {
+        let opcode = wat2wasm(opcode).map_err(|e| ModuleError::ValidateFail(e.to_string()))?;
+        let mut functions = 0;
+        let mut globals = 0;
+        let mut memory_pages = 0;
+
+        // scan - sections
+        for payload in Parser::new(0).parse_all(&opcode) {
+            match payload.map_err(|e| ModuleError::ValidateFail(e.to_string()))? {
+                Payload::ImportSection(reader) => {
+                    // check - import count before reading every import
+                    policy.check(ModuleLimit::Imports, reader.count() as u64)?;
+                    for import in reader {
+                        let import =
+                            import.map_err(|e| ModuleError::ValidateFail(e.to_string()))?;
+                        match import.ty {
+                            TypeRef::Func(_) => functions += 1,
+                            TypeRef::Global(global) => {
+                                globals += 1;
+                                if policy.forbid_float_ops {
+                                    let context =
+                                        format!("import {}.{}", import.module, import.name);
+                                    Self::check_float_type(global.content_type, &context)?;
+                                }
+                            }
+                            TypeRef::Memory(memory) => {
+                                memory_pages = memory_pages.max(Self::memory_pages(&memory))
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                Payload::ExportSection(reader) => {
+                    policy.check(ModuleLimit::Exports, reader.count() as u64)?;
+                }
+                Payload::FunctionSection(reader) => functions += reader.count() as u64,
+                Payload::GlobalSection(reader) => {
+                    globals += reader.count() as u64;
+                    if policy.forbid_float_ops {
+                        for global in reader {
+                            let global =
+                                global.map_err(|e| ModuleError::ValidateFail(e.to_string()))?;
+                            Self::check_float_type(global.ty.content_type, "global")?;
+                        }
+                    }
+                }
+                Payload::TypeSection(reader) if policy.forbid_float_ops => {
+                    // check - fn signatures ( params & results )
+                    for func_type in reader.into_iter_err_on_gc_types() {
+                        let func_type =
+                            func_type.map_err(|e| ModuleError::ValidateFail(e.to_string()))?;
+                        for ty in func_type.params().iter().chain(func_type.results()) {
+                            Self::check_float_type(*ty, "type")?;
+                        }
+                    }
+                }
+                Payload::CodeSectionEntry(body) if policy.forbid_float_ops => {
+                    // check - locals
+                    let locals = body
+                        .get_locals_reader()
+                        .map_err(|e| ModuleError::ValidateFail(e.to_string()))?;
+                    for local in locals {
+                        let (_count, ty) =
+                            local.map_err(|e| ModuleError::ValidateFail(e.to_string()))?;
+                        Self::check_float_type(ty, "local")?;
+                    }
+
+                    // check - float ops ( first offending operator )
+                    let mut reader = body
+                        .get_operators_reader()
+                        .map_err(|e| ModuleError::ValidateFail(e.to_string()))?;
+                    while !reader.eof() {
+                        let operator = reader
+                            .read()
+                            .map_err(|e| ModuleError::ValidateFail(e.to_string()))?;
+                        if Self::is_float_op(&operator) {
+                            return Err(ModuleError::FloatOpForbidden(Self::operator_name(
+                                &operator,
+                            )));
+                        }
+                    }
+                }
+                Payload::MemorySection(reader) => {
+                    for memory in reader {
+                        let memory =
+                            memory.map_err(|e| ModuleError::ValidateFail(e.to_string()))?;
+                        memory_pages = memory_pages.max(Self::memory_pages(&memory));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // check - totals
+        policy.check(ModuleLimit::Functions, functions)?;
+        policy.check(ModuleLimit::Globals, globals)?;
+        policy.check(ModuleLimit::MemoryPages, memory_pages)?;
+
+        Ok(())
+    }
+
+    fn check_float_type(ty: ValType, context: &str) -> Result<(), ModuleError> {
+        match ty {
+            ValType::F32 | ValType::F64 => Err(ModuleError::FloatOpForbidden(format!(
+                "{} {:?}",
+                context, ty
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    // float operator ( scalar & simd ) or a float typed block / select
+    fn is_float_op(operator: &Operator) -> bool {
+        match operator {
+            Operator::Block { blockty } | Operator::Loop { blockty } | Operator::If { blockty } => {
+                matches!(blockty, BlockType::Type(ValType::F32 | ValType::F64))
+            }
+            Operator::TypedSelect { ty } => matches!(ty, ValType::F32 | ValType::F64),
+            Operator::F32Load { .. }
+            | Operator::F64Load { .. }
+            | Operator::F32Store { .. }
+            | Operator::F64Store { .. }
+            | Operator::F32Const { .. }
+            | Operator::F64Const { .. }
+            | Operator::F32Eq { .. }
+            | Operator::F32Ne { .. }
+            | Operator::F32Lt { .. }
+            | Operator::F32Gt { .. }
+            | Operator::F32Le { .. }
+            | Operator::F32Ge { .. }
+            | Operator::F64Eq { .. }
+            | Operator::F64Ne { .. }
+            | Operator::F64Lt { .. }
+            | Operator::F64Gt { .. }
+            | Operator::F64Le { .. }
+            | Operator::F64Ge { .. }
+            | Operator::F32Abs { .. }
+            | Operator::F32Neg { .. }
+            | Operator::F32Ceil { .. }
+            | Operator::F32Floor { .. }
+            | Operator::F32Trunc { .. }
+            | Operator::F32Nearest { .. }
+            | Operator::F32Sqrt { .. }
+            | Operator::F32Add { .. }
+            | Operator::F32Sub { .. }
+            | Operator::F32Mul { .. }
+            | Operator::F32Div { .. }
+            | Operator::F32Min { .. }
+            | Operator::F32Max { .. }
+            | Operator::F32Copysign { .. }
+            | Operator::F64Abs { .. }
+            | Operator::F64Neg { .. }
+            | Operator::F64Ceil { .. }
+            | Operator::F64Floor { .. }
+            | Operator::F64Trunc { .. }
+            | Operator::F64Nearest { .. }
+            | Operator::F64Sqrt { .. }
+            | Operator::F64Add { .. }
+            | Operator::F64Sub { .. }
+            | Operator::F64Mul { .. }
+            | Operator::F64Div { .. }
+            | Operator::F64Min { .. }
+            | Operator::F64Max { .. }
+            | Operator::F64Copysign { .. }
+            | Operator::I32TruncF32S { .. }
+            | Operator::I32TruncF32U { .. }
+            | Operator::I32TruncF64S { .. }
+            | Operator::I32TruncF64U { .. }
+            | Operator::I64TruncF32S { .. }
+            | Operator::I64TruncF32U { .. }
+            | Operator::I64TruncF64S { .. }
+            | Operator::I64TruncF64U { .. }
+            | Operator::F32ConvertI32S { .. }
+            | Operator::F32ConvertI32U { .. }
+            | Operator::F32ConvertI64S { .. }
+            | Operator::F32ConvertI64U { .. }
+            | Operator::F32DemoteF64 { .. }
+            | Operator::F64ConvertI32S { .. }
+            | Operator::F64ConvertI32U { .. }
+            | Operator::F64ConvertI64S { .. }
+            | Operator::F64ConvertI64U { .. }
+            | Operator::F64PromoteF32 { .. }
+            | Operator::I32ReinterpretF32 { .. }
+            | Operator::I64ReinterpretF64 { .. }
+            | Operator::F32ReinterpretI32 { .. }
+            | Operator::F64ReinterpretI64 { .. }
+            | Operator::I32TruncSatF32S { .. }
+            | Operator::I32TruncSatF32U { .. }
+            | Operator::I32TruncSatF64S { .. }
+            | Operator::I32TruncSatF64U { .. }
+            | Operator::I64TruncSatF32S { .. }
+            | Operator::I64TruncSatF32U { .. }
+            | Operator::I64TruncSatF64S { .. }
+            | Operator::I64TruncSatF64U { .. }
+            | Operator::F32x4ExtractLane { .. }
+            | Operator::F32x4ReplaceLane { .. }
+            | Operator::F64x2ExtractLane { .. }
+            | Operator::F64x2ReplaceLane { .. }
+            | Operator::F32x4Splat { .. }
+            | Operator::F64x2Splat { .. }
+            | Operator::F32x4Eq { .. }
+            | Operator::F32x4Ne { .. }
+            | Operator::F32x4Lt { .. }
+            | Operator::F32x4Gt { .. }
+            | Operator::F32x4Le { .. }
+            | Operator::F32x4Ge { .. }
+            | Operator::F64x2Eq { .. }
+            | Operator::F64x2Ne { .. }
+            | Operator::F64x2Lt { .. }
+            | Operator::F64x2Gt { .. }
+            | Operator::F64x2Le { .. }
+            | Operator::F64x2Ge { .. }
+            | Operator::F32x4Ceil { .. }
+            | Operator::F32x4Floor { .. }
+            | Operator::F32x4Trunc { .. }
+            | Operator::F32x4Nearest { .. }
+            | Operator::F32x4Abs { .. }
+            | Operator::F32x4Neg { .. }
+            | Operator::F32x4Sqrt { .. }
+            | Operator::F32x4Add { .. }
+            | Operator::F32x4Sub { .. }
+            | Operator::F32x4Mul { .. }
+            | Operator::F32x4Div { .. }
+            | Operator::F32x4Min { .. }
+            | Operator::F32x4Max { .. }
+            | Operator::F32x4PMin { .. }
+            | Operator::F32x4PMax { .. }
+            | Operator::F64x2Ceil { .. }
+            | Operator::F64x2Floor { .. }
+            | Operator::F64x2Trunc { .. }
+            | Operator::F64x2Nearest { .. }
+            | Operator::F64x2Abs { .. }
+            | Operator::F64x2Neg { .. }
+            | Operator::F64x2Sqrt { .. }
+            | Operator::F64x2Add { .. }
+            | Operator::F64x2Sub { .. }
+            | Operator::F64x2Mul { .. }
+            | Operator::F64x2Div { .. }
+            | Operator::F64x2Min { .. }
+            | Operator::F64x2Max { .. }
+            | Operator::F64x2PMin { .. }
+            | Operator::F64x2PMax { .. }
+            | Operator::I32x4TruncSatF32x4S { .. }
+            | Operator::I32x4TruncSatF32x4U { .. }
+            | Operator::F32x4ConvertI32x4S { .. }
+            | Operator::F32x4ConvertI32x4U { .. }
+            | Operator::I32x4TruncSatF64x2SZero { .. }
+            | Operator::I32x4TruncSatF64x2UZero { .. }
+            | Operator::F64x2ConvertLowI32x4S { .. }
+            | Operator::F64x2ConvertLowI32x4U { .. }
+            | Operator::F32x4DemoteF64x2Zero { .. }
+            | Operator::F64x2PromoteLowF32x4 { .. }
+            | Operator::I32x4RelaxedTruncF32x4S { .. }
+            | Operator::I32x4RelaxedTruncF32x4U { .. }
+            | Operator::I32x4RelaxedTruncF64x2SZero { .. }
+            | Operator::I32x4RelaxedTruncF64x2UZero { .. }
+            | Operator::F32x4RelaxedMadd { .. }
+            | Operator::F32x4RelaxedNmadd { .. }
+            | Operator::F64x2RelaxedMadd { .. }
+            | Operator::F64x2RelaxedNmadd { .. }
+            | Operator::F32x4RelaxedMin { .. }
+            | Operator::F32x4RelaxedMax { .. }
+            | Operator::F64x2RelaxedMin { .. }
+            | Operator::F64x2RelaxedMax { .. } => true,
+            _ => false,
+        }
+    }
+
+    fn memory_pages(memory: &MemoryType) -> u64 {
+        memory.maximum.unwrap_or(memory.initial).max(memory.initial)
+    }
+
+    // check - wasm `start` section ( runs implicitly at instantiation )
+    pub fn has_start_function(opcode: &[u8]) -> Result<bool, ModuleError> {
+        let opcode = wat2wasm(opcode).map_err(|e| ModuleError::ValidateFail(e.to_string()))?;
+        for payload in Parser::new(0).parse_all(&opcode) {
+            match payload {
+                Ok(Payload::StartSection { .. }) => return Ok(true),
+                Ok(_) => {}
+                Err(e) => return Err(ModuleError::ValidateFail(e.to_string())),
+            }
+        }
+
+        Ok(false)
+    }
+
+    // list - distinct operator names in code bodies ( sorted, e.g. "I32Add" ), for schedule audits
+    pub fn operators(opcode: &[u8]) -> Result<Vec<String>, ModuleError> {
+        let opcode = wat2wasm(opcode).map_err(|e| ModuleError::ValidateFail(e.to_string()))?;
+        let mut names = BTreeSet::new();
+        for payload in Parser::new(0).parse_all(&opcode) {
+            let Payload::CodeSectionEntry(body) =
+                payload.map_err(|e| ModuleError::ValidateFail(e.to_string()))?
+            else {
+                continue;
+            };
+
+            // read - operators
+            let mut reader = body
+                .get_operators_reader()
+                .map_err(|e| ModuleError::ValidateFail(e.to_string()))?;
+            while !reader.eof() {
+                let operator = reader
+                    .read()
+                    .map_err(|e| ModuleError::ValidateFail(e.to_string()))?;
+                names.insert(Self::operator_name(&operator));
+            }
+        }
+
+        Ok(names.into_iter().collect())
+    }
+
+    // name - debug repr up to the first field ( "I32Const { value: 1 }" -> "I32Const" )
+    fn operator_name(operator: &Operator) -> String {
+        let debug = format!("{:?}", operator);
+        debug
+            .split([' ', '{', '('])
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    // read - custom section data ( raw wasm, first section with `name` )
+    pub fn read_custom_section(opcode: &[u8], name: &str) -> Option<Vec<u8>> {
+        for payload in Parser::new(0).parse_all(opcode) {
+            if let Ok(Payload::CustomSection(section)) = payload
+                && section.name() == name
+            {
+                return Some(section.data().to_vec());
+            }
+        }
+
+        None
+    }
+
+    // list - custom section names ( raw wasm, sorted & distinct )
+    pub fn list_custom_sections(opcode: &[u8]) -> Vec<String> {
+        let mut names = BTreeSet::new();
+        for payload in Parser::new(0).parse_all(opcode) {
+            match payload {
+                Ok(Payload::CustomSection(section)) => {
+                    names.insert(section.name().to_string());
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+
+        names.into_iter().collect()
+    }
+
+    // list - export names of the compiled module ( sorted & distinct )
+    pub fn list_exports(&self) -> Vec<String> {
+        let Some(module) = self.op_module.as_ref() else {
+            return vec![];
+        };
+        let names: BTreeSet<String> = module
+            .exports()
+            .map(|export| export.name().to_string())
+            .collect();
+
+        names.into_iter().collect()
+    }
+
+    // check - start fn of the loaded module ( serialized modules carry no raw wasm to scan )
+    pub fn has_start(&self) -> bool {
+        self.op_module
+            .as_ref()
+            .is_some_and(|module| module.info().start_function.is_some())
+    }
+
+    // index in the function index space ( imported fns first ), None for non-fn exports
+    pub fn function_index(&self, name: &str) -> Option<u32> {
+        let module = self.op_module.as_ref()?;
+        match module.info().exports.get(name)? {
+            ExportIndex::Function(index) => Some(index.as_u32()),
+            _ => None,
+        }
+    }
+
     pub fn borrow(&mut self) -> &wasmer::Module {
         self.op_module.as_ref().unwrap()
     }
@@ -63,4 +658,25 @@ impl VmModule {
 
         Ok(module_bytes.to_vec())
     }
+
+    // prefix - magic & sha256, so any corrupted byte fails `import_module_opcode`
+    pub fn seal_module_opcode(encoded_module: &[u8]) -> Vec<u8> {
+        let hash = ModuleHash::sha256(encoded_module);
+        [Self::SEALED_MODULE_MAGIC, hash.as_bytes(), encoded_module].concat()
+    }
+
+    // strip - magic & sha256 after checking it, unsealed bytes are returned as is
+    pub fn unseal_module_opcode(encoded_module: &[u8]) -> Result<&[u8], ModuleError> {
+        let Some(sealed) = encoded_module.strip_prefix(Self::SEALED_MODULE_MAGIC) else {
+            return Ok(encoded_module);
+        };
+        let Some((hash, encoded_module)) = sealed.split_at_checked(32) else {
+            return Err(ModuleError::EncodedModuleChecksumInvalid);
+        };
+        if hash != ModuleHash::sha256(encoded_module).as_bytes() {
+            return Err(ModuleError::EncodedModuleChecksumInvalid);
+        }
+
+        Ok(encoded_module)
+    }
 }
@@ -0,0 +1,101 @@
+use std::sync::Mutex;
+
+use wasmer::wasmparser::{BlockType, Operator};
+use wasmer::{
+    AsStoreMut, ExportIndex, FunctionMiddleware, GlobalInit, GlobalType, Instance,
+    LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+};
+use wasmer_types::{GlobalIndex, ModuleInfo};
+
+// Tracks the memory high-water mark. Every `memory.grow` on memory 0 is
+// followed by `memory.size`, and the peak pages global is raised when the
+// memory got bigger; a trap later in the run keeps the peak. A fresh instance
+// starts the global from 0, the launcher takes the max with the current size.
+//
+// Like `Metering`, one instance instruments one module at a time.
+#[derive(Debug, Default)]
+pub struct MemoryPeak {
+    global_index: Mutex<Option<GlobalIndex>>,
+}
+
+impl MemoryPeak {
+    pub const GLOBAL_PEAK_PAGES: &'static str = "wasmer_memory_peak_pages";
+
+    pub fn new() -> Self {
+        MemoryPeak::default()
+    }
+
+    // peak pages grown by the guest, 0 without the middleware or growth
+    pub fn get_pages(store: &mut impl AsStoreMut, instance: &Instance) -> u32 {
+        match instance.exports.get_global(Self::GLOBAL_PEAK_PAGES) {
+            Ok(global) => global.get(store).i32().map_or(0, |pages| pages as u32),
+            Err(_) => 0,
+        }
+    }
+}
+
+impl ModuleMiddleware for MemoryPeak {
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        let peak = self.global_index.lock().unwrap().unwrap();
+
+        Box::new(FunctionMemoryPeak {
+            peak: peak.as_u32(),
+        })
+    }
+
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) -> Result<(), MiddlewareError> {
+        // add - peak pages global
+        let peak = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+        module_info.exports.insert(
+            Self::GLOBAL_PEAK_PAGES.to_string(),
+            ExportIndex::Global(peak),
+        );
+
+        *self.global_index.lock().unwrap() = Some(peak);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct FunctionMemoryPeak {
+    peak: u32,
+}
+
+impl FunctionMiddleware for FunctionMemoryPeak {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        match operator {
+            Operator::MemoryGrow { mem: 0 } => {
+                state.push_operator(operator);
+
+                // peak = max(peak, memory.size) ( the grow result stays on the stack )
+                state.extend(&[
+                    Operator::MemorySize { mem: 0 },
+                    Operator::GlobalGet {
+                        global_index: self.peak,
+                    },
+                    Operator::I32GtU,
+                    Operator::If {
+                        blockty: BlockType::Empty,
+                    },
+                    Operator::MemorySize { mem: 0 },
+                    Operator::GlobalSet {
+                        global_index: self.peak,
+                    },
+                    Operator::End,
+                ]);
+            }
+            _ => state.push_operator(operator),
+        }
+
+        Ok(())
+    }
+}
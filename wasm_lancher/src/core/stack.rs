@@ -0,0 +1,170 @@
+use std::sync::Mutex;
+
+use wasmer::wasmparser::{BlockType, Operator};
+use wasmer::{
+    AsStoreMut, ExportIndex, FunctionMiddleware, GlobalInit, GlobalType, Instance,
+    LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+    Value,
+};
+use wasmer_types::{GlobalIndex, ModuleInfo};
+
+// Limits the wasm call depth. Every call increments a depth global before
+// the callee runs and decrements it once the callee returns, so every way out
+// of the callee ( end, return, br to the function label ) is covered; once the
+// frames go past `max_depth` the exceeded flag is set and the guest traps with
+// `unreachable`. Tail calls reuse the frame and are not counted.
+//
+// Like `Metering`, one instance instruments one module at a time.
+#[derive(Debug)]
+pub struct StackHeightLimit {
+    max_depth: u32,
+    global_indexes: Mutex<Option<(GlobalIndex, GlobalIndex)>>, // ( depth, exceeded )
+}
+
+impl StackHeightLimit {
+    pub const DEF_MAX_DEPTH: u32 = 1024;
+    pub const GLOBAL_DEPTH: &'static str = "wasmer_stack_height_depth";
+    pub const GLOBAL_EXCEEDED: &'static str = "wasmer_stack_height_exceeded";
+
+    pub fn new(max_depth: u32) -> Self {
+        StackHeightLimit {
+            max_depth,
+            global_indexes: Mutex::new(None),
+        }
+    }
+
+    pub fn reset(store: &mut impl AsStoreMut, instance: &Instance) {
+        // reset - depth & flag ( a trap leaves both behind )
+        for name in [Self::GLOBAL_DEPTH, Self::GLOBAL_EXCEEDED] {
+            if let Ok(global) = instance.exports.get_global(name) {
+                let _ = global.set(store, Value::I32(0));
+            }
+        }
+    }
+
+    pub fn is_exceeded(store: &mut impl AsStoreMut, instance: &Instance) -> bool {
+        match instance.exports.get_global(Self::GLOBAL_EXCEEDED) {
+            Ok(global) => global.get(store).i32() == Some(1),
+            Err(_) => false,
+        }
+    }
+}
+
+impl Default for StackHeightLimit {
+    fn default() -> Self {
+        StackHeightLimit::new(Self::DEF_MAX_DEPTH)
+    }
+}
+
+impl ModuleMiddleware for StackHeightLimit {
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        let (depth, exceeded) = self.global_indexes.lock().unwrap().unwrap();
+
+        Box::new(FunctionStackHeightLimit {
+            max_depth: self.max_depth,
+            depth: depth.as_u32(),
+            exceeded: exceeded.as_u32(),
+        })
+    }
+
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) -> Result<(), MiddlewareError> {
+        // add - depth global
+        let depth = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+        module_info
+            .exports
+            .insert(Self::GLOBAL_DEPTH.to_string(), ExportIndex::Global(depth));
+
+        // add - exceeded flag global
+        let exceeded = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+        module_info.exports.insert(
+            Self::GLOBAL_EXCEEDED.to_string(),
+            ExportIndex::Global(exceeded),
+        );
+
+        *self.global_indexes.lock().unwrap() = Some((depth, exceeded));
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct FunctionStackHeightLimit {
+    max_depth: u32,
+    depth: u32,
+    exceeded: u32,
+}
+
+impl FunctionStackHeightLimit {
+    fn enter<'a>(&self, state: &mut MiddlewareReaderState<'a>) {
+        // depth += 1, trap when the callee frame is over the limit ( the root frame counts as 1 )
+        state.extend(&[
+            Operator::GlobalGet {
+                global_index: self.depth,
+            },
+            Operator::I32Const { value: 1 },
+            Operator::I32Add,
+            Operator::GlobalSet {
+                global_index: self.depth,
+            },
+            Operator::GlobalGet {
+                global_index: self.depth,
+            },
+            Operator::I32Const {
+                value: self.max_depth as i32,
+            },
+            Operator::I32GeU,
+            Operator::If {
+                blockty: BlockType::Empty,
+            },
+            Operator::I32Const { value: 1 },
+            Operator::GlobalSet {
+                global_index: self.exceeded,
+            },
+            Operator::Unreachable,
+            Operator::End,
+        ]);
+    }
+
+    fn leave<'a>(&self, state: &mut MiddlewareReaderState<'a>) {
+        // depth -= 1
+        state.extend(&[
+            Operator::GlobalGet {
+                global_index: self.depth,
+            },
+            Operator::I32Const { value: 1 },
+            Operator::I32Sub,
+            Operator::GlobalSet {
+                global_index: self.depth,
+            },
+        ]);
+    }
+}
+
+impl FunctionMiddleware for FunctionStackHeightLimit {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        // wrap - calls returning to this frame ( args & results stay on the stack )
+        match operator {
+            Operator::Call { .. } | Operator::CallIndirect { .. } | Operator::CallRef { .. } => {
+                self.enter(state);
+                state.push_operator(operator);
+                self.leave(state);
+            }
+            _ => state.push_operator(operator),
+        }
+
+        Ok(())
+    }
+}
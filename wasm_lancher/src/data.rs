@@ -1,9 +1,17 @@
-use wasmer::{AsStoreMut, Instance, Memory, MemoryView, StoreMut};
+use std::sync::Arc;
+use std::time::Instant;
 
+use memory::MemCodec;
+use wasmer::{
+    AsStoreMut, FunctionEnvMut, Instance, Memory, MemoryView, RuntimeError, StoreMut, Value,
+};
+
+use crate::core::gas::*;
 use crate::memory::*;
 
 type Ptr = u32;
 pub const DEF_PTR_ERR: i32 = 0;
+pub const DEF_MAX_CALL_DEPTH: u32 = 1024;
 
 #[derive(Debug, PartialEq)]
 pub enum VmDataError {
@@ -17,6 +25,18 @@ pub enum VmDataError {
 pub struct VmData {
     pub instance: Option<Instance>,
     pub memory: Option<Memory>,
+
+    // host fn -> guest export re-entry depth
+    pub call_depth: u32,
+    pub max_call_depth: u32,
+    pub call_depth_exceeded: bool,
+
+    // length-prefix format for memory_write / memory_read
+    pub mem_codec: Arc<dyn MemCodec + Send + Sync>,
+    pub mem_alloc_fn: String,
+
+    // run deadline, checked by the Interrupt middleware's check fn
+    pub deadline: Option<Instant>,
 }
 
 impl Clone for VmData {
@@ -24,6 +44,12 @@ impl Clone for VmData {
         VmData {
             instance: None,
             memory: None,
+            call_depth: 0,
+            max_call_depth: self.max_call_depth,
+            call_depth_exceeded: false,
+            mem_codec: self.mem_codec.clone(),
+            mem_alloc_fn: self.mem_alloc_fn.clone(),
+            deadline: self.deadline,
         }
     }
 }
@@ -33,11 +59,116 @@ impl VmData {
         VmData {
             instance: None,
             memory: None,
+            call_depth: 0,
+            max_call_depth: DEF_MAX_CALL_DEPTH,
+            call_depth_exceeded: false,
+            mem_codec: Arc::new(memory::Memory),
+            mem_alloc_fn: DEF_MEM_ALLOC_FN.to_string(),
+            deadline: None,
         }
     }
 
     pub fn init(&mut self) {}
 
+    pub fn deadline_passed(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    pub fn call_depth_reset(&mut self) {
+        self.call_depth = 0;
+        self.call_depth_exceeded = false;
+    }
+
+    // call - guest export from inside a host fn, guarded by `max_call_depth`
+    pub fn call_export<T: Send + 'static>(
+        env: &mut FunctionEnvMut<'_, (VmData, Option<T>)>,
+        fn_name: &str,
+        args: &[Value],
+    ) -> Result<Box<[Value]>, RuntimeError> {
+        // check - call depth
+        let (vm_data, _opt_external) = env.data_mut();
+        if vm_data.call_depth >= vm_data.max_call_depth {
+            vm_data.call_depth_exceeded = true;
+            return Err(RuntimeError::new("call depth exceeded"));
+        }
+
+        // export - wasm fn
+        let function = vm_data
+            .instance_get()
+            .ok_or_else(|| RuntimeError::new("instance empty"))?
+            .exports
+            .get_function(fn_name)
+            .map_err(|e| RuntimeError::new(e.to_string()))?
+            .clone();
+        vm_data.call_depth += 1;
+
+        // call - wasm fn
+        let ret = function.call(&mut env.as_store_mut(), args);
+        env.data_mut().0.call_depth -= 1;
+
+        ret
+    }
+
+    // write - data from inside a host fn, the allocator is called through `call_export` so the
+    // re-entry counts toward `max_call_depth`
+    pub fn memory_write_export<T: Send + 'static>(
+        env: &mut FunctionEnvMut<'_, (VmData, Option<T>)>,
+        data: &[u8],
+    ) -> Result<Ptr, RuntimeError> {
+        // alloc - guest buffer
+        let (vm_data, _opt_external) = env.data();
+        let mem_codec = vm_data.mem_codec.clone();
+        let mem_alloc_fn = vm_data.mem_alloc_fn.clone();
+        let size = VmMemory::mem_alloc_size(data.len(), mem_codec.as_ref())
+            .map_err(|e| RuntimeError::new(format!("{:?}", VmDataError::MemoryWriteFail(e))))?;
+        let ret = VmData::call_export(env, &mem_alloc_fn, &[Value::I32(size as i32)])?;
+        let ptr = match ret.first() {
+            Some(Value::I32(ptr)) => *ptr as Ptr,
+            _ => {
+                let e = VmDataError::MemoryWriteFail(EmMemError::MemoryAllocPtrEmpty);
+                return Err(RuntimeError::new(format!("{:?}", e)));
+            }
+        };
+
+        // write - memory
+        let ((vm_data, _opt_external), store) = env.data_and_store_mut();
+        let memory_view = vm_data.memory_get(&store).ok_or_else(|| {
+            RuntimeError::new(format!("{:?}", VmDataError::MemoryWriteInstanceEmpty))
+        })?;
+        VmMemory::mem_write_codec(memory_view, ptr, data, mem_codec.as_ref())
+            .map_err(|e| RuntimeError::new(format!("{:?}", VmDataError::MemoryWriteFail(e))))
+    }
+
+    // charge - host fn work against the remaining metering points ( no-op when unmetered )
+    pub fn charge_gas<T: Send + 'static>(
+        env: &mut FunctionEnvMut<'_, (VmData, Option<T>)>,
+        amount: u64,
+    ) -> Result<(), RuntimeError> {
+        // load - instance
+        let ((vm_data, _opt_external), mut store) = env.data_and_store_mut();
+        let instance = vm_data
+            .instance_get()
+            .ok_or_else(|| RuntimeError::new("instance empty"))?;
+        if !GasMetering::is_metered(instance) {
+            return Ok(());
+        }
+
+        // decrease - gas ( exhaust on shortfall, reported as OutOfGas )
+        if !GasMetering::gas_decrease(&mut store, instance, amount) {
+            GasMetering::set_store_mute(&mut store, instance, 0);
+            return Err(RuntimeError::new("out of gas"));
+        }
+
+        Ok(())
+    }
+
+    pub fn gas_handle<'a, 'e, T: Send + 'static>(
+        env: &'a mut FunctionEnvMut<'e, (VmData, Option<T>)>,
+    ) -> GasHandle<'a, 'e, T> {
+        GasHandle { env }
+    }
+
     pub fn instance_set(&mut self, instance: Instance) {
         self.instance = Some(instance);
     }
@@ -67,8 +198,14 @@ impl VmData {
             .ok_or(VmDataError::MemoryWriteInstanceEmpty)?;
 
         // write - memory
-        let ptr = VmMemory::mem_write_mut_store(store, instance, data)
-            .map_err(|e| VmDataError::MemoryWriteFail(e))?;
+        let ptr = VmMemory::mem_write_mut_store_with(
+            store,
+            instance,
+            data,
+            self.mem_codec.as_ref(),
+            &self.mem_alloc_fn,
+        )
+        .map_err(|e| VmDataError::MemoryWriteFail(e))?;
 
         Ok(ptr)
     }
@@ -87,11 +224,36 @@ impl VmData {
 
         // read - memory
         for ptr in ptr {
-            let vec_u8 = VmMemory::mem_read(&memory_view, ptr as u32)
-                .map_err(|e| VmDataError::MemoryReadFail(e))?;
+            let vec_u8 =
+                VmMemory::mem_read_codec(&memory_view, ptr as u32, self.mem_codec.as_ref())
+                    .map_err(|e| VmDataError::MemoryReadFail(e))?;
             memory_read.push(vec_u8);
         }
 
         Ok(memory_read)
     }
 }
+
+// Host fn view of the call's metering points. It borrows the env mutably, so it must be
+// dropped before `call_export`; a re-entered guest call draws from the same budget.
+pub struct GasHandle<'a, 'e, T: Send + 'static> {
+    env: &'a mut FunctionEnvMut<'e, (VmData, Option<T>)>,
+}
+
+impl<T: Send + 'static> GasHandle<'_, '_, T> {
+    // None when unmetered
+    pub fn remaining(&mut self) -> Option<u64> {
+        let ((vm_data, _opt_external), mut store) = self.env.data_and_store_mut();
+        let instance = vm_data.instance_get()?;
+        if !GasMetering::is_metered(instance) {
+            return None;
+        }
+
+        Some(GasMetering::get_left_store_mute(&mut store, instance))
+    }
+
+    // no-op when unmetered, exhausts the budget on shortfall ( reported as OutOfGas )
+    pub fn charge(&mut self, amount: u64) -> Result<(), RuntimeError> {
+        VmData::charge_gas(self.env, amount)
+    }
+}
@@ -1,56 +1,160 @@
 pub mod core;
 pub mod data;
 pub mod memory;
+pub mod pool;
+pub mod types;
+pub mod units;
 
 use borsh::{BorshDeserialize, BorshSerialize};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
+use std::time::Instant;
 use std::u64;
 
+pub use ::memory::MemCodec;
 pub use wasmer::*;
 use wasmer_middlewares::metering::set_remaining_points;
 pub use wasmparser::Operator;
 
 use crate::core::gas::*;
 use crate::core::instance::*;
+use crate::core::interrupt::*;
 use crate::core::module::*;
+use crate::core::peak::*;
+use crate::core::stack::*;
 use crate::data::*;
 use crate::memory::*;
 
-#[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize, Clone)]
+#[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
 pub enum EmVmError {
     // WasmFunction
     FunctionExportFail(String),
     FunctionCallFail(String),
     FunctionCallOutOfGas,
+    FunctionCallStackHeightExceeded,
+    FunctionCallDepthExceeded,
+    FunctionCallHostPanic(String),
+    GasMeteringDisabled,
+    DeadlineExceeded(DeadlinePhase),
 
     // Initialize
     NewOpcodeBinaryEmpty,
+    NewOpcodeFileReadFail(String),
     NewModuleInitBinaryFail(ModuleError),
     NewModuleInitEncodedFail(ModuleError),
     NewInstanceInitFail(InstanceError),
+    NewInstanceImportMissing(String), // "module.name", non-fn or non-"env" imports
+    NewInstanceImportIncompatible(String),
+    NewInstanceMemoryFail(String),
+    NewInstanceStartTrap(String),
+    MissingRequiredExport(String),
+    ImportMissing(Vec<String>),
+    ImportSignatureMismatch {
+        name: String,
+        expected: String, // declared by the guest
+        got: String,      // supplied by the host
+    },
     ExportModuleFail(ModuleError),
+    ExportModuleVerifyFail(ModuleError),
     RetProgramMemReadFail(EmMemError),
+    RetProgramPtrInvalid(String),
+    RetProgramShapeInvalid(String), // result types, e.g. "[I32, I32, I32]"
+
+    // Memory
+    MemoryWriteFail(EmMemError),
+    MemorySnapshotFail(EmMemError),
+    MemoryRestoreFail(EmMemError),
+    MemorySizeFail(EmMemError),
+    GlobalRestoreFail(String),
+    ResetInstanceFail(InstanceError),
+
+    // Rpc
+    RpcJsonDecodeFail(String),
+    ReceiptDecodeFail(String),
+
+    // Borsh ( run_borsh )
+    BorshInputEncodeFail(String),
+    BorshOutputDecodeFail(String),
+    ProgramBorshEncodeFail, // guest returned ProgramCode::BorshEncodeInvalidArg
+    ProgramBorshDecodeFail, // guest returned ProgramCode::BorshDecodeInvalidArg
+    ProgramFail(ProgramCode),
+}
+
+impl EmVmError {
+    // split - common instantiation failures, anything else stays NewInstanceInitFail
+    fn from_instance_error(e: InstanceError) -> Self {
+        match e {
+            InstanceError::LinkImportUnknown(name) => EmVmError::NewInstanceImportMissing(name),
+            InstanceError::LinkImportIncompatible(name) => {
+                EmVmError::NewInstanceImportIncompatible(name)
+            }
+            InstanceError::LinkMemoryFail(e) => EmVmError::NewInstanceMemoryFail(e),
+            InstanceError::StartTrap(e) => EmVmError::NewInstanceStartTrap(e),
+            e => EmVmError::NewInstanceInitFail(e),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+pub enum DeadlinePhase {
+    Compile,
+    Instantiate,
+    Run,
 }
 
 pub type GasConsumptionFn = Arc<dyn Fn(&Operator) -> u64 + Send + Sync + 'static>;
 
-pub struct VMLauncher<T: Send + Sync + Clone + 'static> {
+#[derive(Debug, Clone, Default)]
+pub struct VmConfig {
+    // None = on for metered ( consensus ) mode, off otherwise
+    pub canonicalize_nans: Option<bool>,
+
+    // extra compiler middlewares ( e.g. StackHeightLimit ), applied after metering
+    pub middlewares: Vec<Arc<dyn ModuleMiddleware>>,
+
+    // None = DEF_MAX_CALL_DEPTH, counts host fn -> guest export re-entries
+    pub max_call_depth: Option<u32>,
+
+    // None = 4byte le length prefix ( memory::Memory ), must match the guest ABI
+    pub mem_codec: Option<Arc<dyn MemCodec + Send + Sync>>,
+
+    // None = DEF_MEM_ALLOC_FN ( "mem_alloc" ), guest allocator export used by host writes
+    pub mem_alloc_fn: Option<String>,
+
+    // limits checked before compiling binary / wat opcode
+    pub module_policy: ModulePolicy,
+
+    // None = reject a wasm `start` fn in metered mode ( it runs at instantiation, outside
+    // run's gas limit, against a 0 budget ), only checked for binary / wat, not encoded modules
+    pub allow_start_function: Option<bool>,
+
+    // check "memory" & allocator exports at init, for hosts passing data to the guest
+    pub require_mem_exports: bool,
+
+    // on OutOfGas, read the buffer the guest's "partial_ret_ptr" global points at ( best-effort )
+    pub out_of_gas_partial_data: bool,
+
+    // instrument memory.grow with the MemoryPeak middleware ( off = mem_peak_bytes is the size
+    // once the call returns, the compiled module is left as is )
+    pub mem_peak_tracked: bool,
+}
+
+// compiled once, then instantiated per launcher ( `from_compiled` ), shareable across threads
+pub struct CompiledModule {
+    engine: Engine,
     vm_module: VmModule,
-    store: Store,
-    instance: Instance,
     gas_used: bool,
-
-    #[allow(dead_code)]
-    external: Option<T>,
+    config: VmConfig,
 }
 
-impl VMLauncher<()> {
+impl CompiledModule {
     pub fn new(
         opcode: &[u8],
         opcode_module_used: bool, // module 압축된 opcode 사용 여부
         gas_metering_used: bool,
         gas_consumption: Option<GasConsumptionFn>,
+        config: VmConfig,
     ) -> Result<Self, EmVmError> {
         // check - opcode binary
         if opcode.is_empty() {
@@ -58,57 +162,232 @@ impl VMLauncher<()> {
         }
 
         // init - gas
-        let mut store: Store;
+        // nan canonicalization defaults to on for metered ( consensus ) mode
+        let canonicalize_nans = config.canonicalize_nans.unwrap_or(gas_metering_used);
+        let mut middlewares = config.middlewares.clone();
+        if config.mem_peak_tracked {
+            middlewares.push(Arc::new(MemoryPeak::new()));
+        }
+        let store: Store;
         let gas_used: bool;
         match gas_metering_used {
             true => {
-                store = Store::new(EngineBuilder::new(GasMetering::create_cfg(gas_consumption)));
+                store = Store::new(EngineBuilder::new(GasMetering::create_cfg(
+                    gas_consumption,
+                    canonicalize_nans,
+                    middlewares,
+                )));
                 gas_used = true;
             }
             false => {
-                store = Store::default();
+                let mut compiler_config = Cranelift::default();
+                compiler_config.canonicalize_nans(canonicalize_nans);
+                for middleware in middlewares {
+                    compiler_config.push_middleware(middleware);
+                }
+                store = Store::new(EngineBuilder::new(compiler_config));
                 gas_used = false;
             }
         }
 
+        // check - start fn
+        let allow_start_function = config.allow_start_function.unwrap_or(!gas_metering_used);
+        if !opcode_module_used && !allow_start_function {
+            let has_start_function =
+                VmModule::has_start_function(opcode).map_err(EmVmError::NewModuleInitBinaryFail)?;
+            if has_start_function {
+                return Err(EmVmError::NewModuleInitBinaryFail(
+                    ModuleError::StartFunctionNotAllowed,
+                ));
+            }
+        }
+
         // init - module
         let mut vm_module = VmModule::new();
         match opcode_module_used {
             true => {
                 vm_module
                     .import_module_opcode(&store, opcode)
-                    .map_err(|e| EmVmError::NewModuleInitEncodedFail(e))?;
+                    .map_err(EmVmError::NewModuleInitEncodedFail)?;
+
+                // check - start fn ( after deserialization )
+                if !allow_start_function && vm_module.has_start() {
+                    return Err(EmVmError::NewModuleInitEncodedFail(
+                        ModuleError::StartFunctionNotAllowed,
+                    ));
+                }
             }
             false => {
                 vm_module
-                    .import(&mut store, opcode)
-                    .map_err(|e| EmVmError::NewModuleInitBinaryFail(e))?;
+                    .import_with_policy(&store, opcode, &config.module_policy)
+                    .map_err(EmVmError::NewModuleInitBinaryFail)?;
             }
         }
 
-        // init - instance
-        let instance = VmInstance::new::<ImportedFn<()>>(
-            &mut store,
-            vm_module.borrow(),
-            VmData::new(),
-            None::<()>,
-            HashMap::new(),
-        )
-        .map_err(|e| EmVmError::NewInstanceInitFail(e))?
-        .unwrap();
+        // check - exports needed for host -> guest writes ( opt-in )
+        if config.require_mem_exports {
+            let mem_alloc_fn = config.mem_alloc_fn.as_deref().unwrap_or(DEF_MEM_ALLOC_FN);
+            Self::check_mem_exports(vm_module.borrow(), mem_alloc_fn)?;
+        }
 
-        Ok(VMLauncher {
+        Ok(CompiledModule {
+            engine: store.engine().clone(),
             vm_module,
-            store,
-            instance,
             gas_used,
-            external: None,
+            config,
         })
     }
+
+    pub fn is_metered(&self) -> bool {
+        self.gas_used
+    }
+
+    fn check_mem_exports(module: &Module, mem_alloc_fn: &str) -> Result<(), EmVmError> {
+        let has_memory = module
+            .exports()
+            .memories()
+            .any(|export| export.name() == "memory");
+        if !has_memory {
+            return Err(EmVmError::MissingRequiredExport("memory".to_string()));
+        }
+
+        let has_mem_alloc = module
+            .exports()
+            .functions()
+            .any(|export| export.name() == mem_alloc_fn);
+        if !has_mem_alloc {
+            return Err(EmVmError::MissingRequiredExport(mem_alloc_fn.to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+pub struct VMLauncher<T: Send + Sync + Clone + 'static> {
+    vm_module: VmModule,
+    store: Store,
+    instance: Instance,
+    imports: Imports,
+    host_fns: SharedImports<T>,
+    vm_env: FunctionEnv<(VmData, Option<T>)>,
+    mem_codec: Arc<dyn MemCodec + Send + Sync>,
+    out_of_gas_partial_data: bool,
+    gas_used: bool,
+
+    external: T, // given at construction, returned by shutdown when a host fn took the env's
+}
+
+impl VMLauncher<()> {
+    // "mmap" feature maps the file instead of reading it onto the heap
+    pub fn from_path(
+        path: impl AsRef<std::path::Path>,
+        opcode_module_used: bool,
+        gas_metering_used: bool,
+        gas_consumption: Option<GasConsumptionFn>,
+    ) -> Result<Self, EmVmError> {
+        let opcode = Self::load_opcode(path.as_ref())?;
+        Self::new(
+            &opcode,
+            opcode_module_used,
+            gas_metering_used,
+            gas_consumption,
+        )
+    }
+
+    #[cfg(all(feature = "mmap", any(unix, windows)))]
+    fn load_opcode(path: &std::path::Path) -> Result<memmap2::Mmap, EmVmError> {
+        let file = std::fs::File::open(path)
+            .map_err(|e| EmVmError::NewOpcodeFileReadFail(e.to_string()))?;
+
+        // the file must not be modified while mapped
+        unsafe { memmap2::Mmap::map(&file) }
+            .map_err(|e| EmVmError::NewOpcodeFileReadFail(e.to_string()))
+    }
+
+    #[cfg(not(all(feature = "mmap", any(unix, windows))))]
+    fn load_opcode(path: &std::path::Path) -> Result<Vec<u8>, EmVmError> {
+        std::fs::read(path).map_err(|e| EmVmError::NewOpcodeFileReadFail(e.to_string()))
+    }
+
+    // `new_with_external_deadline` without host fns
+    pub fn new_with_deadline(
+        opcode: &[u8],
+        opcode_module_used: bool,
+        gas_metering_used: bool,
+        gas_consumption: Option<GasConsumptionFn>,
+        deadline: Instant,
+    ) -> Result<Self, EmVmError> {
+        Self::new_with_external_deadline(
+            opcode,
+            opcode_module_used,
+            gas_metering_used,
+            (),
+            HashMap::new(),
+            gas_consumption,
+            VmConfig::default(),
+            deadline,
+        )
+    }
+
+    // compile only ( no store state, instance or host fns ), for deploy-time checks
+    pub fn validate(
+        opcode: &[u8],
+        opcode_module_used: bool,
+        gas_metering_used: bool,
+    ) -> Result<(), EmVmError> {
+        CompiledModule::new(
+            opcode,
+            opcode_module_used,
+            gas_metering_used,
+            None,
+            VmConfig::default(),
+        )?;
+        Ok(())
+    }
+
+    pub fn from_compiled(compiled: Arc<CompiledModule>) -> Result<Self, EmVmError> {
+        Self::init_compiled::<ImportedFn<()>>(&compiled, (), HashMap::new())
+    }
+
+    pub fn new(
+        opcode: &[u8],
+        opcode_module_used: bool, // module 압축된 opcode 사용 여부
+        gas_metering_used: bool,
+        gas_consumption: Option<GasConsumptionFn>,
+    ) -> Result<Self, EmVmError> {
+        Self::new_with_config(
+            opcode,
+            opcode_module_used,
+            gas_metering_used,
+            gas_consumption,
+            VmConfig::default(),
+        )
+    }
+
+    pub fn new_with_config(
+        opcode: &[u8],
+        opcode_module_used: bool, // module 압축된 opcode 사용 여부
+        gas_metering_used: bool,
+        gas_consumption: Option<GasConsumptionFn>,
+        config: VmConfig,
+    ) -> Result<Self, EmVmError> {
+        Self::init::<ImportedFn<()>>(
+            opcode,
+            opcode_module_used,
+            gas_metering_used,
+            gas_consumption,
+            config,
+            (),
+            HashMap::new(),
+        )
+    }
 }
 
 impl<T: Send + Sync + Clone + 'static> VMLauncher<T> {
     pub const DEF_PROGRAM_RET_EMPTY: Vec<u8> = Vec::new();
+    pub const DEF_FUEL_PRIORITY: u64 = 1;
+    pub const DEF_METERING_GLOBAL_PREFIX: &'static str = "wasmer_metering_";
+    pub const DEF_PARTIAL_RET_GLOBAL: &'static str = "partial_ret_ptr";
 
     pub fn new_with_external(
         opcode: &[u8],
@@ -118,64 +397,404 @@ impl<T: Send + Sync + Clone + 'static> VMLauncher<T> {
         imported_fn: HashMap<String, (ImportedFn<T>, FunctionType)>,
         gas_consumption: Option<GasConsumptionFn>,
     ) -> Result<Self, EmVmError> {
-        // check - opcode binary
-        if opcode.is_empty() {
-            return Err(EmVmError::NewOpcodeBinaryEmpty);
-        }
+        Self::new_with_external_config(
+            opcode,
+            opcode_module_used,
+            gas_metering_used,
+            external,
+            imported_fn,
+            gas_consumption,
+            VmConfig::default(),
+        )
+    }
 
-        // init - gas
-        let mut store: Store;
-        let gas_used: bool;
-        match gas_metering_used {
-            true => {
-                store = Store::new(EngineBuilder::new(GasMetering::create_cfg(gas_consumption)));
-                gas_used = true;
-            }
-            false => {
-                store = Store::default();
-                gas_used = false;
-            }
-        }
+    pub fn new_with_external_config(
+        opcode: &[u8],
+        opcode_module_used: bool, // module 압축된 opcode 사용 여부
+        gas_metering_used: bool,
+        external: T,
+        imported_fn: HashMap<String, (ImportedFn<T>, FunctionType)>,
+        gas_consumption: Option<GasConsumptionFn>,
+        config: VmConfig,
+    ) -> Result<Self, EmVmError> {
+        Self::init(
+            opcode,
+            opcode_module_used,
+            gas_metering_used,
+            gas_consumption,
+            config,
+            external,
+            imported_fn,
+        )
+    }
 
+    // one deadline for compile + instantiate + runs. compilation can't be aborted, so it is only
+    // checked once compile / instantiate return; a running guest is interrupted at the deadline
+    // ( Interrupt is added to `config.middlewares` )
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_external_deadline(
+        opcode: &[u8],
+        opcode_module_used: bool, // module 압축된 opcode 사용 여부
+        gas_metering_used: bool,
+        external: T,
+        imported_fn: HashMap<String, (ImportedFn<T>, FunctionType)>,
+        gas_consumption: Option<GasConsumptionFn>,
+        mut config: VmConfig,
+        deadline: Instant,
+    ) -> Result<Self, EmVmError> {
         // init - module
-        let mut vm_module = VmModule::new();
-        match opcode_module_used {
-            true => {
-                vm_module
-                    .import_module_opcode(&store, opcode)
-                    .map_err(|e| EmVmError::NewModuleInitEncodedFail(e))?;
-            }
-            false => {
-                vm_module
-                    .import(&mut store, opcode)
-                    .map_err(|e| EmVmError::NewModuleInitBinaryFail(e))?;
-            }
+        config.middlewares.push(Arc::new(Interrupt::new()));
+        let compiled = CompiledModule::new(
+            opcode,
+            opcode_module_used,
+            gas_metering_used,
+            gas_consumption,
+            config,
+        )?;
+        if Instant::now() >= deadline {
+            return Err(EmVmError::DeadlineExceeded(DeadlinePhase::Compile));
         }
 
         // init - instance
-        let instance = VmInstance::new(
-            &mut store,
-            vm_module.borrow(),
-            VmData::new(),
-            Some(external.clone()),
-            imported_fn,
-        )
-        .map_err(|e| EmVmError::NewInstanceInitFail(e))?
-        .unwrap();
+        let mut launcher = Self::init_compiled(&compiled, external, imported_fn)?;
+        if Instant::now() >= deadline {
+            return Err(EmVmError::DeadlineExceeded(DeadlinePhase::Instantiate));
+        }
+
+        launcher.set_deadline(Some(deadline));
+        Ok(launcher)
+    }
+
+    fn init<F>(
+        opcode: &[u8],
+        opcode_module_used: bool,
+        gas_metering_used: bool,
+        gas_consumption: Option<GasConsumptionFn>,
+        config: VmConfig,
+        external: T,
+        imported_fn: HashMap<String, (F, FunctionType)>,
+    ) -> Result<Self, EmVmError>
+    where
+        F: Fn(
+                FunctionEnvMut<'_, (VmData, Option<T>)>,
+                &[Value],
+            ) -> Result<Vec<Value>, RuntimeError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let compiled = CompiledModule::new(
+            opcode,
+            opcode_module_used,
+            gas_metering_used,
+            gas_consumption,
+            config,
+        )?;
+        Self::init_compiled(&compiled, external, imported_fn)
+    }
+
+    pub fn from_compiled_with_external(
+        compiled: Arc<CompiledModule>,
+        external: T,
+        imported_fn: HashMap<String, (ImportedFn<T>, FunctionType)>,
+    ) -> Result<Self, EmVmError> {
+        Self::init_compiled(&compiled, external, imported_fn)
+    }
+
+    fn init_compiled<F>(
+        compiled: &CompiledModule,
+        external: T,
+        imported_fn: HashMap<String, (F, FunctionType)>,
+    ) -> Result<Self, EmVmError>
+    where
+        F: Fn(
+                FunctionEnvMut<'_, (VmData, Option<T>)>,
+                &[Value],
+            ) -> Result<Vec<Value>, RuntimeError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        // init - store ( per launcher, sharing the compiled module's engine )
+        let mut store = Store::new(compiled.engine.clone());
+        let mut vm_module = compiled.vm_module.clone();
+        let config = &compiled.config;
+
+        // check - host fn signatures against guest imports
+        Self::check_imports(vm_module.borrow(), &imported_fn)?;
+
+        // init - instance
+        let mut vm_data = VmData::new();
+        vm_data.max_call_depth = config.max_call_depth.unwrap_or(DEF_MAX_CALL_DEPTH);
+        if let Some(mem_codec) = &config.mem_codec {
+            vm_data.mem_codec = mem_codec.clone();
+        }
+        if let Some(mem_alloc_fn) = &config.mem_alloc_fn {
+            vm_data.mem_alloc_fn = mem_alloc_fn.clone();
+        }
+        let mem_codec = vm_data.mem_codec.clone();
+        let host_fns = VmInstance::share(imported_fn);
+        let (imports, vm_env) =
+            VmInstance::imports_shared(&mut store, vm_data, Some(external.clone()), &host_fns);
+        let instance = VmInstance::instantiate(&mut store, vm_module.borrow(), &imports, &vm_env)
+            .map_err(EmVmError::from_instance_error)?;
 
         Ok(VMLauncher {
             vm_module,
             store,
             instance,
-            gas_used,
-            external: Some(external),
+            imports,
+            host_fns,
+            vm_env,
+            mem_codec,
+            out_of_gas_partial_data: config.out_of_gas_partial_data,
+            gas_used: compiled.gas_used,
+            external,
         })
     }
 
+    fn check_imports<F>(
+        module: &Module,
+        imported_fn: &HashMap<String, (F, FunctionType)>,
+    ) -> Result<(), EmVmError> {
+        let mut missing = BTreeSet::new();
+        for import in module.imports() {
+            // check - only "env" fns are supplied by the host
+            let ExternType::Function(guest_fn_type) = import.ty() else {
+                continue;
+            };
+            if import.module() != "env" {
+                continue;
+            }
+
+            // check - signature
+            let Some((_, host_fn_type)) = imported_fn.get(import.name()) else {
+                missing.insert(import.name().to_string());
+                continue;
+            };
+            if host_fn_type != guest_fn_type {
+                return Err(EmVmError::ImportSignatureMismatch {
+                    name: import.name().to_string(),
+                    expected: guest_fn_type.to_string(),
+                    got: host_fn_type.to_string(),
+                });
+            }
+        }
+
+        // check - missing fns ( sorted, stable across runs )
+        if !missing.is_empty() {
+            return Err(EmVmError::ImportMissing(missing.into_iter().collect()));
+        }
+
+        Ok(())
+    }
+
     pub fn run(&mut self, gas_priority: u64, gas_limit: u64, fn_name: &str) -> VmRunResult {
-        // set - gas limit
+        self.call(gas_priority, gas_limit, fn_name, &[])
+            .with_refund(gas_limit)
+    }
+
+    // Fuel is an absolute budget : remaining points are set to `fuel` directly
+    // and `gas_used` is `fuel - gas_left`. This matches `run(1, fuel, ..)`, since
+    // a priority of 1 leaves the limit unscaled.
+    pub fn run_with_fuel(&mut self, fuel: u64, fn_name: &str, args: &[Value]) -> VmRunResult {
+        self.call(Self::DEF_FUEL_PRIORITY, fuel, fn_name, args)
+            .with_refund(fuel)
+    }
+
+    // guest memory & exported mutable globals are rolled back after the call ( memory grown by
+    // the call keeps its size, zeroed ), host-side `external` state is not
+    pub fn run_speculative(
+        &mut self,
+        gas_priority: u64,
+        gas_limit: u64,
+        fn_name: &str,
+        args: &[Value],
+    ) -> VmRunResult {
+        // snapshot - memory & globals
+        let memory = match self.snapshot_memory() {
+            Ok(memory) => memory,
+            Err(e) => {
+                return VmRunResult::new(
+                    Some(e),
+                    ProgramCode::VmError,
+                    Self::DEF_PROGRAM_RET_EMPTY,
+                    0,
+                )
+                .with_refund(gas_limit);
+            }
+        };
+        let globals = self.snapshot_globals();
+
+        // run
+        let vm_ret = self
+            .call(gas_priority, gas_limit, fn_name, args)
+            .with_refund(gas_limit);
+
+        // rollback - memory & globals
+        if let Err(e) = self.rollback(&memory, &globals) {
+            let mut rollback_ret = VmRunResult::new(
+                Some(e),
+                ProgramCode::VmError,
+                Self::DEF_PROGRAM_RET_EMPTY,
+                vm_ret.gas_used,
+            );
+            rollback_ret.mem_peak_bytes = vm_ret.mem_peak_bytes;
+            return rollback_ret.with_refund(gas_limit);
+        }
+
+        vm_ret
+    }
+
+    fn rollback(&mut self, memory: &[u8], globals: &[(String, Value)]) -> Result<(), EmVmError> {
+        self.restore_memory(memory)?;
+
+        // clear - pages grown past the snapshot ( memory can't shrink )
+        let mem_size = self.memory_size_bytes()?;
+        if mem_size > memory.len() as u64 {
+            let zeros = vec![0; (mem_size - memory.len() as u64) as usize];
+            self.with_initial_memory_at(memory.len() as u64, &zeros)?;
+        }
+
+        self.restore_globals(globals)
+    }
+
+    // calls share the instance & one gas_limit, stops after the first failed call
+    pub fn run_sequence(
+        &mut self,
+        gas_priority: u64,
+        gas_limit: u64,
+        calls: &[(String, Vec<Value>)],
+    ) -> Vec<VmRunResult> {
+        let mut vm_rets = Vec::with_capacity(calls.len());
+        let mut gas_left = gas_limit;
+        for (fn_name, args) in calls {
+            // run - against the gas left by the previous calls
+            let vm_ret = self
+                .call(gas_priority, gas_left, fn_name, args)
+                .with_refund(gas_left);
+            gas_left = gas_left.saturating_sub(vm_ret.gas_used);
+
+            // check - stop ( error, out of gas or a guest error code )
+            let stop = vm_ret.error.is_some()
+                || !matches!(vm_ret.program_code, ProgramCode::Ok | ProgramCode::NoReturn);
+            vm_rets.push(vm_ret);
+            if stop {
+                break;
+            }
+        }
+
+        vm_rets
+    }
+
+    // borsh `input` -> run_with_input -> borsh output ( the Ok program data )
+    pub fn run_borsh<I: BorshSerialize, O: BorshDeserialize>(
+        &mut self,
+        gas_priority: u64,
+        gas_limit: u64,
+        fn_name: &str,
+        input: &I,
+    ) -> Result<O, EmVmError> {
+        // encode - input
+        let input =
+            borsh::to_vec(input).map_err(|e| EmVmError::BorshInputEncodeFail(e.to_string()))?;
+
+        // run
+        let vm_ret = self.run_with_input(gas_priority, gas_limit, fn_name, &input);
+        if let Some(e) = vm_ret.error {
+            return Err(e);
+        }
+
+        // decode - output
+        match vm_ret.program_code {
+            ProgramCode::Ok => O::try_from_slice(&vm_ret.program_data)
+                .map_err(|e| EmVmError::BorshOutputDecodeFail(e.to_string())),
+            ProgramCode::BorshEncodeInvalidArg => Err(EmVmError::ProgramBorshEncodeFail),
+            ProgramCode::BorshDecodeInvalidArg => Err(EmVmError::ProgramBorshDecodeFail),
+            program_code => Err(EmVmError::ProgramFail(program_code)),
+        }
+    }
+
+    // `input` is written with the configured codec & allocator, its ptr is the only arg. the
+    // write is part of the run ( the allocator's gas is charged, deadline & guards apply )
+    pub fn run_with_input(
+        &mut self,
+        gas_priority: u64,
+        gas_limit: u64,
+        fn_name: &str,
+        input: &[u8],
+    ) -> VmRunResult {
+        self.call_guarded(gas_priority, gas_limit, fn_name, &[], Some(input))
+            .with_refund(gas_limit)
+    }
+
+    fn call(
+        &mut self,
+        gas_priority: u64,
+        gas_limit: u64,
+        fn_name: &str,
+        args: &[Value],
+    ) -> VmRunResult {
+        self.call_guarded(gas_priority, gas_limit, fn_name, args, None)
+    }
+
+    fn call_guarded(
+        &mut self,
+        gas_priority: u64,
+        gas_limit: u64,
+        fn_name: &str,
+        args: &[Value],
+        input: Option<&[u8]>,
+    ) -> VmRunResult {
+        // check - deadline ( before the call )
+        if self.deadline_passed() {
+            let mut vm_ret = VmRunResult::new(
+                Some(EmVmError::DeadlineExceeded(DeadlinePhase::Run)),
+                ProgramCode::Timeout,
+                Self::DEF_PROGRAM_RET_EMPTY,
+                0,
+            );
+            vm_ret.mem_peak_bytes = self.mem_peak_bytes();
+            return vm_ret;
+        }
+
+        // call - interrupted by the Interrupt middleware's check fn once the deadline passes
+        let mut vm_ret = self.call_fn(gas_priority, gas_limit, fn_name, args, input);
+
+        // check - deadline ( an interrupted or late result is dropped, the gas it used is still charged )
+        if self.deadline_passed() {
+            vm_ret = VmRunResult::new(
+                Some(EmVmError::DeadlineExceeded(DeadlinePhase::Run)),
+                ProgramCode::Timeout,
+                Self::DEF_PROGRAM_RET_EMPTY,
+                vm_ret.gas_used,
+            );
+        }
+
+        // load - memory peak ( every result, traps included )
+        vm_ret.mem_peak_bytes = self.mem_peak_bytes();
+        vm_ret
+    }
+
+    // peak since instantiation / `reset` : pages grown by the guest ( `mem_peak_tracked` ) or the
+    // current size, whichever is bigger ( host side growth isn't hooked )
+    fn mem_peak_bytes(&mut self) -> u64 {
+        let peak =
+            MemoryPeak::get_pages(&mut self.store, &self.instance) as u64 * WASM_PAGE_SIZE as u64;
+        peak.max(self.memory_size_bytes().unwrap_or(0))
+    }
+
+    fn call_fn(
+        &mut self,
+        gas_priority: u64,
+        gas_limit: u64,
+        fn_name: &str,
+        args: &[Value],
+        input: Option<&[u8]>,
+    ) -> VmRunResult {
+        // set - gas limit ( unmetered launchers have no points to set )
         let mut gas_limit_calc = 0;
-        if gas_priority != 0 {
+        if self.gas_used && gas_priority != 0 {
             gas_limit_calc = self.calc_gas(gas_priority, gas_limit);
             set_remaining_points(&mut self.store, &self.instance, gas_limit_calc);
         }
@@ -191,16 +810,90 @@ impl<T: Send + Sync + Clone + 'static> VMLauncher<T> {
             );
         }
 
+        // reset - stack height ( no-op without the StackHeightLimit middleware )
+        StackHeightLimit::reset(&mut self.store, &self.instance);
+
+        // reset - call depth
+        self.vm_env.as_mut(&mut self.store).0.call_depth_reset();
+
+        // write - input, its ptr replaces the args ( the allocator runs on the call's gas )
+        let input_args: [Value; 1];
+        let args = match input {
+            Some(input) => {
+                let mem_alloc_fn = self.vm_env.as_ref(&self.store).0.mem_alloc_fn.clone();
+                match VmMemory::mem_write_store_with(
+                    &mut self.store,
+                    &self.instance,
+                    input,
+                    self.mem_codec.as_ref(),
+                    &mem_alloc_fn,
+                ) {
+                    Ok(ptr) => {
+                        input_args = [Value::I32(ptr as i32)];
+                        &input_args[..]
+                    }
+                    Err(e) => {
+                        return match self.get_gas_left() {
+                            0 if self.gas_used => VmRunResult::new(
+                                Some(EmVmError::FunctionCallOutOfGas),
+                                ProgramCode::OutOfGas,
+                                Self::DEF_PROGRAM_RET_EMPTY,
+                                gas_limit,
+                            ),
+                            u64_gas_left => VmRunResult::new(
+                                Some(EmVmError::MemoryWriteFail(e)),
+                                ProgramCode::FnInvalidArgs,
+                                Self::DEF_PROGRAM_RET_EMPTY,
+                                Self::gas_charged(
+                                    gas_limit_calc,
+                                    u64_gas_left,
+                                    gas_priority,
+                                    gas_limit,
+                                ),
+                            ),
+                        };
+                    }
+                }
+            }
+            None => args,
+        };
+
         // call - wasm fn
-        let ret_box_value = ret_fn.unwrap().call(&mut self.store, &[]);
+        let ret_box_value = ret_fn.unwrap().call(&mut self.store, args);
         if let Err(e) = ret_box_value {
             let u64_gas_left = self.get_gas_left();
+            if let Some(host_panic) = e.downcast_ref::<HostPanic>() {
+                return VmRunResult::new(
+                    Some(EmVmError::FunctionCallHostPanic(host_panic.0.clone())),
+                    ProgramCode::HostPanic,
+                    Self::DEF_PROGRAM_RET_EMPTY,
+                    Self::gas_charged(gas_limit_calc, u64_gas_left, gas_priority, gas_limit),
+                );
+            }
+            if StackHeightLimit::is_exceeded(&mut self.store, &self.instance) {
+                return VmRunResult::new(
+                    Some(EmVmError::FunctionCallStackHeightExceeded),
+                    ProgramCode::StackHeightExceeded,
+                    Self::DEF_PROGRAM_RET_EMPTY,
+                    Self::gas_charged(gas_limit_calc, u64_gas_left, gas_priority, gas_limit),
+                );
+            }
+            if self.vm_env.as_ref(&self.store).0.call_depth_exceeded {
+                return VmRunResult::new(
+                    Some(EmVmError::FunctionCallDepthExceeded),
+                    ProgramCode::CallDepthExceeded,
+                    Self::DEF_PROGRAM_RET_EMPTY,
+                    Self::gas_charged(gas_limit_calc, u64_gas_left, gas_priority, gas_limit),
+                );
+            }
+
+            // check - out of gas ( a trap on an unmetered launcher is never OutOfGas )
             match u64_gas_left {
-                0 => {
+                0 if self.gas_used => {
                     return VmRunResult::new(
                         Some(EmVmError::FunctionCallOutOfGas),
                         ProgramCode::OutOfGas,
-                        Self::DEF_PROGRAM_RET_EMPTY,
+                        self.partial_data(),
                         gas_limit, // 모든 가스 소진하여 입력된 가스 총량 리턴
                     );
                 }
@@ -209,7 +902,7 @@ impl<T: Send + Sync + Clone + 'static> VMLauncher<T> {
                         Some(EmVmError::FunctionCallFail(format!("{:?}", e))),
                         ProgramCode::UnknownError,
                         Self::DEF_PROGRAM_RET_EMPTY,
-                        gas_limit_calc - u64_gas_left,
+                        Self::gas_charged(gas_limit_calc, u64_gas_left, gas_priority, gas_limit),
                     );
                 }
             }
@@ -223,7 +916,7 @@ impl<T: Send + Sync + Clone + 'static> VMLauncher<T> {
         // 최종 가스 소모량을 계산할때 priority 를 곱해줘야 한다.
         self.ret_program(
             ret_box_value.unwrap(),
-            (gas_limit_calc - gas_left) * gas_priority,
+            Self::gas_charged(gas_limit_calc, gas_left, gas_priority, gas_limit),
         )
     }
 
@@ -236,8 +929,238 @@ impl<T: Send + Sync + Clone + 'static> VMLauncher<T> {
         Ok(module_bytes)
     }
 
-    fn get_gas_left(&mut self) -> u64 {
-        match self.gas_used {
+    // sealed with a checksum ( `VmModule::seal_module_opcode` ), importable like `get_module_opcode`
+    pub fn export_verified_module(&mut self) -> Result<Vec<u8>, EmVmError> {
+        // export - module
+        let module_bytes = VmModule::seal_module_opcode(&self.get_module_opcode()?);
+
+        // verify - round-trip before handing the bytes out
+        self.verify_module_opcode(&module_bytes)?;
+
+        Ok(module_bytes)
+    }
+
+    pub fn verify_module_opcode(&self, module_bytes: &[u8]) -> Result<(), EmVmError> {
+        VmModule::verify_module_opcode(self.store.engine(), module_bytes)
+            .map_err(EmVmError::ExportModuleVerifyFail)
+    }
+
+    pub fn reset(&mut self) -> Result<(), EmVmError> {
+        // new - store ( same engine, the old store drops every old instance, memory & global )
+        let mut store = Store::new(self.store.engine().clone());
+
+        // new - env & imports ( same config & external, same host fns )
+        let (vm_data, external) = self.vm_env.as_ref(&self.store);
+        let (vm_data, external) = (vm_data.clone(), external.clone());
+        let (imports, vm_env) =
+            VmInstance::imports_shared(&mut store, vm_data, external, &self.host_fns);
+
+        // new - instance ( fresh memory & globals )
+        let instance =
+            VmInstance::instantiate(&mut store, self.vm_module.borrow(), &imports, &vm_env)
+                .map_err(EmVmError::ResetInstanceFail)?;
+
+        self.store = store;
+        self.instance = instance;
+        self.imports = imports;
+        self.vm_env = vm_env;
+
+        Ok(())
+    }
+
+    // drop - store & instance, returning the env's external ( including host fn updates ),
+    // the construction value when a host fn took it out of the env
+    pub fn shutdown(mut self) -> T {
+        let external = self.vm_env.as_mut(&mut self.store).1.take();
+        external.unwrap_or(self.external)
+    }
+
+    pub fn with_initial_memory(&mut self, bytes: &[u8]) -> Result<(), EmVmError> {
+        self.with_initial_memory_at(0, bytes)
+    }
+
+    pub fn with_initial_memory_at(&mut self, offset: u64, bytes: &[u8]) -> Result<(), EmVmError> {
+        // write - memory ( must fit in the current memory size )
+        VmMemory::mem_write_raw_store(&mut self.store, &self.instance, offset, bytes)
+            .map_err(EmVmError::MemoryWriteFail)
+    }
+
+    // write - `data` with the configured codec & allocator, freed with "mem_free" once `f` returns
+    pub fn with_guest_buffer<R>(
+        &mut self,
+        data: &[u8],
+        f: impl FnOnce(&mut Store, &Instance, u32) -> R,
+    ) -> Result<R, EmVmError> {
+        let mem_alloc_fn = self.vm_env.as_ref(&self.store).0.mem_alloc_fn.clone();
+        let instance = &self.instance;
+        VmMemory::with_guest_buffer_with(
+            &mut self.store,
+            instance,
+            data,
+            self.mem_codec.as_ref(),
+            &mem_alloc_fn,
+            |store, ptr| f(store, instance, ptr),
+        )
+        .map_err(EmVmError::MemoryWriteFail)
+    }
+
+    // list - guest exports ( sorted ), minus metering, stack height, interrupt & memory peak globals
+    pub fn list_exports(&self) -> Vec<String> {
+        self.vm_module
+            .list_exports()
+            .into_iter()
+            .filter(|name| !name.starts_with(Self::DEF_METERING_GLOBAL_PREFIX))
+            .filter(|name| {
+                name.as_str() != StackHeightLimit::GLOBAL_DEPTH
+                    && name.as_str() != StackHeightLimit::GLOBAL_EXCEEDED
+                    && name.as_str() != MemoryPeak::GLOBAL_PEAK_PAGES
+            })
+            .collect()
+    }
+
+    pub fn function_index(&self, name: &str) -> Option<u32> {
+        self.vm_module.function_index(name)
+    }
+
+    pub fn memory_size_bytes(&self) -> Result<u64, EmVmError> {
+        VmMemory::mem_size(&self.store, &self.instance).map_err(EmVmError::MemorySizeFail)
+    }
+
+    // Snapshot / restore cover the guest side only ( linear memory & exported
+    // mutable globals ). Host-side `external` state is never captured.
+    pub fn snapshot_memory(&mut self) -> Result<Vec<u8>, EmVmError> {
+        VmMemory::mem_snapshot_store(&mut self.store, &self.instance)
+            .map_err(EmVmError::MemorySnapshotFail)
+    }
+
+    pub fn restore_memory(&mut self, snapshot: &[u8]) -> Result<(), EmVmError> {
+        // write - memory ( grows memory when the snapshot is larger )
+        VmMemory::mem_restore_store(&mut self.store, &self.instance, snapshot)
+            .map_err(EmVmError::MemoryRestoreFail)
+    }
+
+    // debug - every exported global ( mutable or not ), minus metering & stack height globals
+    pub fn read_globals(&mut self) -> Vec<(String, Value)> {
+        let globals: Vec<(String, Global)> = self
+            .instance
+            .exports
+            .iter()
+            .globals()
+            .filter(|(name, _)| !name.starts_with(Self::DEF_METERING_GLOBAL_PREFIX))
+            .filter(|(name, _)| {
+                name.as_str() != StackHeightLimit::GLOBAL_DEPTH
+                    && name.as_str() != StackHeightLimit::GLOBAL_EXCEEDED
+                    && name.as_str() != MemoryPeak::GLOBAL_PEAK_PAGES
+            })
+            .map(|(name, global)| (name.clone(), global.clone()))
+            .collect();
+
+        globals
+            .into_iter()
+            .map(|(name, global)| (name, global.get(&mut self.store)))
+            .collect()
+    }
+
+    pub fn snapshot_globals(&mut self) -> Vec<(String, Value)> {
+        // load - exported mutable globals ( metering & memory peak globals belong to the launcher )
+        let globals: Vec<(String, Global)> = self
+            .instance
+            .exports
+            .iter()
+            .globals()
+            .filter(|(name, _)| !name.starts_with(Self::DEF_METERING_GLOBAL_PREFIX))
+            .filter(|(name, _)| name.as_str() != MemoryPeak::GLOBAL_PEAK_PAGES)
+            .filter(|(_, global)| global.ty(&self.store).mutability == Mutability::Var)
+            .map(|(name, global)| (name.clone(), global.clone()))
+            .collect();
+
+        // read - global values
+        globals
+            .into_iter()
+            .map(|(name, global)| (name, global.get(&mut self.store)))
+            .collect()
+    }
+
+    pub fn restore_globals(&mut self, globals: &[(String, Value)]) -> Result<(), EmVmError> {
+        for (name, value) in globals {
+            // load - global
+            let global = self
+                .instance
+                .exports
+                .get_global(name)
+                .map_err(|e| EmVmError::GlobalRestoreFail(e.to_string()))?;
+
+            // write - global
+            global
+                .set(&mut self.store, value.clone())
+                .map_err(|e| EmVmError::GlobalRestoreFail(e.message()))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn is_metered(&self) -> bool {
+        self.gas_used
+    }
+
+    // checked around every call, a running guest is only interrupted when the module was
+    // compiled with the Interrupt middleware ( `new_with_external_deadline` / `VmConfig::middlewares` )
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.vm_env.as_mut(&mut self.store).0.deadline = deadline;
+    }
+
+    fn deadline_passed(&self) -> bool {
+        self.vm_env.as_ref(&self.store).0.deadline_passed()
+    }
+
+    // gas outside of run, for externally driven loops ( run still sets its own limit )
+    pub fn set_gas(&mut self, points: u64) -> Result<(), EmVmError> {
+        if !self.gas_used {
+            return Err(EmVmError::GasMeteringDisabled);
+        }
+
+        set_remaining_points(&mut self.store, &self.instance, points);
+        Ok(())
+    }
+
+    pub fn remaining_gas(&mut self) -> Result<u64, EmVmError> {
+        if !self.gas_used {
+            return Err(EmVmError::GasMeteringDisabled);
+        }
+
+        Ok(self.get_gas_left())
+    }
+
+    fn partial_data(&mut self) -> Vec<u8> {
+        if !self.out_of_gas_partial_data {
+            return Self::DEF_PROGRAM_RET_EMPTY;
+        }
+
+        // load - partial ret ptr ( 0 = nothing written yet )
+        let ptr = match self
+            .instance
+            .exports
+            .get_global(Self::DEF_PARTIAL_RET_GLOBAL)
+        {
+            Ok(global) => match global.get(&mut self.store) {
+                Value::I32(ptr) if ptr > 0 => ptr as u32,
+                _ => return Self::DEF_PROGRAM_RET_EMPTY,
+            },
+            Err(_) => return Self::DEF_PROGRAM_RET_EMPTY,
+        };
+
+        // read - memory ( bounds checked, any failure = empty )
+        VmMemory::mem_read_store_codec(
+            &mut self.store,
+            &self.instance,
+            ptr,
+            self.mem_codec.as_ref(),
+        )
+        .unwrap_or(Self::DEF_PROGRAM_RET_EMPTY)
+    }
+
+    fn get_gas_left(&mut self) -> u64 {
+        match self.gas_used {
             true => GasMetering::get_left(&mut self.store, &self.instance),
             false => return 0,
         }
@@ -247,23 +1170,63 @@ impl<T: Send + Sync + Clone + 'static> VMLauncher<T> {
         gas_limit / gas_priority
     }
 
+    // consumed points x priority, clamped to gas_limit ( the most the caller can owe ) on overflow
+    // and when points left exceed the calc limit ( priority 0 keeps the points set before )
+    fn gas_charged(gas_limit_calc: u64, gas_left: u64, gas_priority: u64, gas_limit: u64) -> u64 {
+        gas_limit_calc
+            .saturating_sub(gas_left)
+            .checked_mul(gas_priority)
+            .map_or(gas_limit, |gas_used| gas_used.min(gas_limit))
+    }
+
+    fn ret_ptr(value: &Value) -> Result<u32, EmVmError> {
+        // convert - i32 / i64 ptr ( negative or > u32 ptr is invalid )
+        match value {
+            Value::I32(ptr) => u32::try_from(*ptr).map_err(|_| {
+                EmVmError::RetProgramPtrInvalid(format!("ptr out of range : {}", ptr))
+            }),
+            Value::I64(ptr) => u32::try_from(*ptr).map_err(|_| {
+                EmVmError::RetProgramPtrInvalid(format!("ptr out of range : {}", ptr))
+            }),
+            _ => Err(EmVmError::RetProgramPtrInvalid(format!(
+                "ptr type unsupported : {:?}",
+                value.ty()
+            ))),
+        }
+    }
+
     fn ret_program(&mut self, value: Box<[Value]>, gas_used: u64) -> VmRunResult {
-        // check - empty
+        // check - empty ( void fn )
         if value.is_empty() {
             return VmRunResult::new(
                 None,
-                ProgramCode::UnknownError,
+                ProgramCode::NoReturn,
                 Self::DEF_PROGRAM_RET_EMPTY,
                 gas_used,
             );
         }
 
-        // load - ptr
-        let ptr = match value[0].i32() {
-            Some(ptr) => ptr as u32,
-            None => {
+        // load - ptr ( `ptr` or `( code, ptr )`, every slot i32 / i64, the result's code byte
+        // decides the program code ), any other shape is a vm error
+        let is_int = |value: &Value| matches!(value, Value::I32(_) | Value::I64(_));
+        let ptr_value = match &value[..] {
+            [ptr] if is_int(ptr) => ptr,
+            [code, ptr] if is_int(code) && is_int(ptr) => ptr,
+            _ => {
+                let types: Vec<Type> = value.iter().map(|value| value.ty()).collect();
+                return VmRunResult::new(
+                    Some(EmVmError::RetProgramShapeInvalid(format!("{:?}", types))),
+                    ProgramCode::VmError,
+                    Self::DEF_PROGRAM_RET_EMPTY,
+                    gas_used,
+                );
+            }
+        };
+        let ptr = match Self::ret_ptr(ptr_value) {
+            Ok(ptr) => ptr,
+            Err(e) => {
                 return VmRunResult::new(
-                    None,
+                    Some(e),
                     ProgramCode::UndefinedErrPtr,
                     Self::DEF_PROGRAM_RET_EMPTY,
                     gas_used,
@@ -271,8 +1234,29 @@ impl<T: Send + Sync + Clone + 'static> VMLauncher<T> {
             }
         };
 
+        // check - ptr ( header within current memory )
+        if let Ok(memory) = self.instance.exports.get_memory("memory") {
+            let mem_size = memory.view(&self.store).data_size();
+            if ptr as u64 + self.mem_codec.header_size() as u64 > mem_size {
+                return VmRunResult::new(
+                    Some(EmVmError::RetProgramPtrInvalid(format!(
+                        "ptr out of bounds : {} ( memory size {} )",
+                        ptr, mem_size
+                    ))),
+                    ProgramCode::UndefinedErrPtr,
+                    Self::DEF_PROGRAM_RET_EMPTY,
+                    gas_used,
+                );
+            }
+        }
+
         // read - memory ( in wasm )
-        let result = match VmMemory::mem_read_store(&mut self.store, &self.instance, ptr) {
+        let result = match VmMemory::mem_read_store_codec(
+            &mut self.store,
+            &self.instance,
+            ptr,
+            self.mem_codec.as_ref(),
+        ) {
             Ok(result) => result,
             Err(e) => {
                 return VmRunResult::new(
@@ -284,31 +1268,40 @@ impl<T: Send + Sync + Clone + 'static> VMLauncher<T> {
             }
         };
 
+        // check - code byte ( a 0 len result has none )
+        if result.is_empty() {
+            return VmRunResult::new(
+                Some(EmVmError::RetProgramPtrInvalid(format!(
+                    "program result empty : {}",
+                    ptr
+                ))),
+                ProgramCode::UndefinedErrPtr,
+                Self::DEF_PROGRAM_RET_EMPTY,
+                gas_used,
+            );
+        }
+
         // load - program ret type
         let program_err = ProgramCode::from_arr_u8(&result[0..1]);
         match program_err {
-            // proc - code ok
+            // proc - code ok ( Ok with no payload is valid, program_data is empty )
             ProgramCode::Ok => {
                 let fn_ret_data = result[1..].to_vec();
                 VmRunResult::new(None, ProgramCode::Ok, fn_ret_data, gas_used)
             }
 
-            // proc - code error & abort
-            _ => {
-                let program_ret_code_bytes = result;
-                let program_ret_code = ProgramCode::from_arr_u8(&program_ret_code_bytes);
-                VmRunResult::new(
-                    None,
-                    program_ret_code,
-                    Self::DEF_PROGRAM_RET_EMPTY,
-                    gas_used,
-                )
-            }
+            // proc - code error & abort ( first byte only, any trailing data is dropped )
+            program_ret_code => VmRunResult::new(
+                None,
+                program_ret_code,
+                Self::DEF_PROGRAM_RET_EMPTY,
+                gas_used,
+            ),
         }
     }
 }
 
-#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
 pub enum ProgramCode {
     Ok,
     FnInvalidEntryPoint,
@@ -323,6 +1316,12 @@ pub enum ProgramCode {
 
     BorshEncodeInvalidArg,
     BorshDecodeInvalidArg,
+
+    StackHeightExceeded,
+    CallDepthExceeded,
+    NoReturn,
+    HostPanic,
+    Timeout,
 }
 
 impl ProgramCode {
@@ -344,6 +1343,13 @@ impl ProgramCode {
             x if x == ProgramCode::BorshDecodeInvalidArg.to_vec_u8() => {
                 ProgramCode::BorshDecodeInvalidArg
             }
+            x if x == ProgramCode::StackHeightExceeded.to_vec_u8() => {
+                ProgramCode::StackHeightExceeded
+            }
+            x if x == ProgramCode::CallDepthExceeded.to_vec_u8() => ProgramCode::CallDepthExceeded,
+            x if x == ProgramCode::NoReturn.to_vec_u8() => ProgramCode::NoReturn,
+            x if x == ProgramCode::HostPanic.to_vec_u8() => ProgramCode::HostPanic,
+            x if x == ProgramCode::Timeout.to_vec_u8() => ProgramCode::Timeout,
             _ => ProgramCode::UnknownError,
         }
     }
@@ -366,6 +1372,13 @@ impl ProgramCode {
             ProgramCode::BorshDecodeInvalidArg => {
                 vec![ProgramCode::BorshDecodeInvalidArg.to_i32() as u8]
             }
+            ProgramCode::StackHeightExceeded => {
+                vec![ProgramCode::StackHeightExceeded.to_i32() as u8]
+            }
+            ProgramCode::CallDepthExceeded => vec![ProgramCode::CallDepthExceeded.to_i32() as u8],
+            ProgramCode::NoReturn => vec![ProgramCode::NoReturn.to_i32() as u8],
+            ProgramCode::HostPanic => vec![ProgramCode::HostPanic.to_i32() as u8],
+            ProgramCode::Timeout => vec![ProgramCode::Timeout.to_i32() as u8],
         }
     }
 
@@ -385,6 +1398,11 @@ impl ProgramCode {
             x if x == ProgramCode::BorshDecodeInvalidArg.to_i32() => {
                 ProgramCode::BorshDecodeInvalidArg
             }
+            x if x == ProgramCode::StackHeightExceeded.to_i32() => ProgramCode::StackHeightExceeded,
+            x if x == ProgramCode::CallDepthExceeded.to_i32() => ProgramCode::CallDepthExceeded,
+            x if x == ProgramCode::NoReturn.to_i32() => ProgramCode::NoReturn,
+            x if x == ProgramCode::HostPanic.to_i32() => ProgramCode::HostPanic,
+            x if x == ProgramCode::Timeout.to_i32() => ProgramCode::Timeout,
             _ => ProgramCode::UnknownError,
         }
     }
@@ -401,6 +1419,32 @@ impl ProgramCode {
             ProgramCode::VmError => ProgramCode::VmError as i32,
             ProgramCode::BorshEncodeInvalidArg => ProgramCode::BorshEncodeInvalidArg as i32,
             ProgramCode::BorshDecodeInvalidArg => ProgramCode::BorshDecodeInvalidArg as i32,
+            ProgramCode::StackHeightExceeded => ProgramCode::StackHeightExceeded as i32,
+            ProgramCode::CallDepthExceeded => ProgramCode::CallDepthExceeded as i32,
+            ProgramCode::NoReturn => ProgramCode::NoReturn as i32,
+            ProgramCode::HostPanic => ProgramCode::HostPanic as i32,
+            ProgramCode::Timeout => ProgramCode::Timeout as i32,
+        }
+    }
+
+    // default status for http gateways ( guest faults = 4xx, vm / host faults = 5xx )
+    pub fn http_status(&self) -> u16 {
+        match self {
+            ProgramCode::Ok => 200,
+            ProgramCode::NoReturn => 200,
+            ProgramCode::FnInvalidEntryPoint => 404,
+            ProgramCode::FnInvalidIndex => 400,
+            ProgramCode::FnInvalidArgs => 400,
+            ProgramCode::BorshEncodeInvalidArg => 400,
+            ProgramCode::BorshDecodeInvalidArg => 400,
+            ProgramCode::OutOfGas => 402,
+            ProgramCode::StackHeightExceeded => 422,
+            ProgramCode::CallDepthExceeded => 422,
+            ProgramCode::UndefinedErrPtr => 500,
+            ProgramCode::UnknownError => 500,
+            ProgramCode::VmError => 500,
+            ProgramCode::HostPanic => 500,
+            ProgramCode::Timeout => 504,
         }
     }
 }
@@ -411,6 +1455,8 @@ pub struct VmRunResult {
     pub program_code: ProgramCode,
     pub program_data: Vec<u8>,
     pub gas_used: u64,
+    pub gas_refund: u64,
+    pub mem_peak_bytes: u64,
 }
 
 impl VmRunResult {
@@ -425,17 +1471,632 @@ impl VmRunResult {
             program_code,
             program_data,
             gas_used,
+            gas_refund: 0,
+            mem_peak_bytes: 0,
+        }
+    }
+
+    // borsh byte length, counted without building the buffer
+    pub fn encoded_len(&self) -> usize {
+        // the length writer has no io to fail on
+        borsh::object_length(self).unwrap()
+    }
+
+    pub fn with_refund(mut self, gas_limit: u64) -> Self {
+        // out of gas already charges the whole limit, so nothing is refunded
+        self.gas_refund = match self.program_code {
+            ProgramCode::OutOfGas => 0,
+            _ => gas_limit.saturating_sub(self.gas_used),
+        };
+        self
+    }
+
+    pub fn to_rpc_json(&self) -> String {
+        let rpc = VmRunResultRpc {
+            error: self.error.as_ref().map(VmErrorRpc::from_error),
+            program_code: self.program_code.clone(),
+            program_data: format!("0x{}", hex::encode(&self.program_data)),
+            gas_used: self.gas_used,
+            gas_refund: self.gas_refund,
+            mem_peak_bytes: self.mem_peak_bytes,
+        };
+
+        // field order is fixed by VmRunResultRpc, so the output is deterministic
+        serde_json::to_string(&rpc).unwrap()
+    }
+
+    pub fn from_rpc_json(json: &str) -> Result<Self, EmVmError> {
+        let rpc: VmRunResultRpc =
+            serde_json::from_str(json).map_err(|e| EmVmError::RpcJsonDecodeFail(e.to_string()))?;
+
+        // decode - program data ( 0x hex )
+        let program_data = rpc
+            .program_data
+            .strip_prefix("0x")
+            .ok_or_else(|| EmVmError::RpcJsonDecodeFail("program_data missing 0x".to_string()))?;
+        let program_data =
+            hex::decode(program_data).map_err(|e| EmVmError::RpcJsonDecodeFail(e.to_string()))?;
+
+        Ok(VmRunResult {
+            error: rpc.error.map(VmErrorRpc::into_error).transpose()?,
+            program_code: rpc.program_code,
+            program_data,
+            gas_used: rpc.gas_used,
+            gas_refund: rpc.gas_refund,
+            mem_peak_bytes: rpc.mem_peak_bytes,
+        })
+    }
+
+    // [ tag ( 0 ok path / 1 error ) ][ program_code ][ gas_used ( leb128 ) ]
+    // [ data len ( leb128 ) ][ data ][ borsh error ( tag 1 only ) ]
+    // gas_refund & mem_peak_bytes are not kept
+    pub fn to_receipt_bytes(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(8 + self.program_data.len());
+        buffer.push(self.error.is_some() as u8);
+        buffer.push(self.program_code.to_i32() as u8);
+        Self::write_leb128(&mut buffer, self.gas_used);
+        Self::write_leb128(&mut buffer, self.program_data.len() as u64);
+        buffer.extend_from_slice(&self.program_data);
+        if let Some(error) = &self.error {
+            buffer.extend_from_slice(&borsh::to_vec(error).unwrap());
+        }
+
+        buffer
+    }
+
+    pub fn from_receipt_bytes(bytes: &[u8]) -> Result<Self, EmVmError> {
+        let err = |msg: &str| EmVmError::ReceiptDecodeFail(msg.to_string());
+
+        // decode - header
+        let (tag, program_code) = match bytes {
+            [tag, program_code, ..] => (*tag, ProgramCode::from_i32(*program_code as i32)),
+            _ => return Err(err("receipt too short")),
+        };
+        let mut offset = 2;
+        let gas_used = Self::read_leb128(bytes, &mut offset).ok_or_else(|| err("gas_used"))?;
+        let data_len = Self::read_leb128(bytes, &mut offset).ok_or_else(|| err("data len"))?;
+
+        // decode - program data
+        let data_end = usize::try_from(data_len)
+            .ok()
+            .and_then(|data_len| offset.checked_add(data_len))
+            .filter(|data_end| *data_end <= bytes.len())
+            .ok_or_else(|| err("data out of range"))?;
+        let program_data = bytes[offset..data_end].to_vec();
+
+        // decode - error
+        let error = match tag {
+            0 if data_end == bytes.len() => None,
+            1 => Some(
+                EmVmError::try_from_slice(&bytes[data_end..])
+                    .map_err(|e| EmVmError::ReceiptDecodeFail(e.to_string()))?,
+            ),
+            _ => return Err(err("invalid tag or trailing bytes")),
+        };
+
+        Ok(VmRunResult::new(
+            error,
+            program_code,
+            program_data,
+            gas_used,
+        ))
+    }
+
+    fn write_leb128(buffer: &mut Vec<u8>, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                buffer.push(byte);
+                return;
+            }
+            buffer.push(byte | 0x80);
+        }
+    }
+
+    fn read_leb128(bytes: &[u8], offset: &mut usize) -> Option<u64> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = *bytes.get(*offset)?;
+            *offset += 1;
+            value |= ((byte & 0x7f) as u64).checked_shl(shift)?;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
         }
+
+        None
+    }
+}
+
+// json shape of VmRunResult for rpc, borsh stays the canonical encoding
+#[derive(Serialize, Deserialize)]
+struct VmRunResultRpc {
+    error: Option<VmErrorRpc>,
+    program_code: ProgramCode,
+    program_data: String,
+    gas_used: u64,
+    gas_refund: u64,
+    mem_peak_bytes: u64,
+}
+
+// one json shape for every error variant, detail is null for unit variants
+#[derive(Serialize, Deserialize)]
+struct VmErrorRpc {
+    kind: String,
+    detail: serde_json::Value,
+}
+
+impl VmErrorRpc {
+    fn from_error(error: &EmVmError) -> Self {
+        // split - serde's external tag ( "Kind" / { "Kind": detail } )
+        let (kind, detail) = match serde_json::to_value(error).unwrap() {
+            serde_json::Value::String(kind) => (kind, serde_json::Value::Null),
+            serde_json::Value::Object(map) => map.into_iter().next().unwrap(),
+            value => unreachable!("externally tagged enum : {}", value),
+        };
+
+        VmErrorRpc { kind, detail }
+    }
+
+    fn into_error(self) -> Result<EmVmError, EmVmError> {
+        // join - back into serde's external tag
+        let value = match self.detail {
+            serde_json::Value::Null => serde_json::Value::String(self.kind),
+            detail => serde_json::Value::Object([(self.kind, detail)].into_iter().collect()),
+        };
+
+        serde_json::from_value(value).map_err(|e| EmVmError::RpcJsonDecodeFail(e.to_string()))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::core::gas::*;
+    use crate::pool::*;
+    use crate::types::*;
+    use crate::units::*;
     use crate::*;
-    use std::{fs, sync::Arc};
+    use std::{collections::HashMap, fs, sync::Arc, time::Instant};
 
     const FILE_PATH_WASM: &str = "main.wasm";
 
+    // loops until gas is exhausted
+    const WAT_SPIN: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "spin") (loop br 0)))
+    "#;
+
+    const WAT_TRAP: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "trap") unreachable))
+    "#;
+
+    // program result [ len = 3 ][ ProgramCode::Ok ][ 0xAA 0xBB ] at ptr 16
+    // [ len = 1 ][ ProgramCode::Ok ] at ptr 32, [ len = 0 ] at ptr 48
+    const WAT_RET_PTR: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (data (i32.const 16) "\03\00\00\00\00\AA\BB")
+            (data (i32.const 32) "\01\00\00\00\00")
+            (data (i32.const 48) "\00\00\00\00")
+            (func (export "ret_ok_empty") (result i32) i32.const 32)
+            (func (export "ret_empty") (result i32) i32.const 48)
+            (func (export "ret_i64") (result i64) i64.const 16)
+            (func (export "ret_multi") (result i32 i32) i32.const 3 i32.const 16)
+            (func (export "ret_triple") (result i32 i32 i32) i32.const 0 i32.const 16 i32.const 16)
+            (func (export "ret_float_code") (result f32 i32) f32.const 0 i32.const 16)
+            (func (export "ret_negative") (result i32) i32.const -1)
+            (func (export "ret_negative_i64") (result i64) i64.const -16)
+            (func (export "ret_out_of_bounds") (result i32) i32.const 65536))
+    "#;
+
+    const WAT_FLOAT: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "float_math")
+                f64.const 1.5
+                f64.const 2.5
+                f64.add
+                f64.const 3.0
+                f64.mul
+                f64.const 4.0
+                f64.div
+                f64.sqrt
+                drop))
+    "#;
+
+    // loops `n` times, then returns an empty Ok result
+    const WAT_WORK: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (data (i32.const 16) "\01\00\00\00\00")
+            (func (export "work") (param $n i32) (result i32)
+                (block $done
+                    (loop $next
+                        local.get $n
+                        i32.eqz
+                        br_if $done
+                        local.get $n
+                        i32.const 1
+                        i32.sub
+                        local.set $n
+                        br $next))
+                i32.const 16))
+    "#;
+
+    // 0.0 / 0.0 ( loaded from memory ) written as raw f64 bits after the Ok code
+    const WAT_NAN: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (data (i32.const 16) "\09\00\00\00\00")
+            (func (export "nan") (result i32)
+                i32.const 21
+                i32.const 64
+                f64.load
+                i32.const 64
+                f64.load
+                f64.div
+                i64.reinterpret_f64
+                i64.store
+                i32.const 16))
+    "#;
+
+    // imports env.host_add : (i32, i32) -> i32
+    const WAT_IMPORT_ADD: &str = r#"
+        (module
+            (import "env" "host_add" (func $host_add (param i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (func (export "add") (result i32)
+                i32.const 1
+                i32.const 2
+                call $host_add))
+    "#;
+
+    // recurses `n` times, then returns an empty Ok result
+    const WAT_RECURSE: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (data (i32.const 16) "\01\00\00\00\00")
+            (func $down (export "down") (param $n i32) (result i32)
+                local.get $n
+                i32.eqz
+                if (result i32)
+                    i32.const 16
+                else
+                    local.get $n
+                    i32.const 1
+                    i32.sub
+                    call $down
+                end))
+    "#;
+
+    // calls fns leaving through `br 0` / `br_if 0` on the function label, `n` times in a loop
+    const WAT_BR_EXIT: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (data (i32.const 16) "\01\00\00\00\00")
+            (func $exit_br (result i32)
+                i32.const 16
+                br 0)
+            (func $exit_br_if (param $taken i32) (result i32)
+                i32.const 16
+                local.get $taken
+                br_if 0)
+            (func (export "loop_br") (param $n i32) (result i32)
+                (loop $again
+                    call $exit_br
+                    drop
+                    i32.const 1
+                    call $exit_br_if
+                    drop
+                    local.get $n
+                    i32.const 1
+                    i32.sub
+                    local.tee $n
+                    br_if $again)
+                i32.const 16))
+    "#;
+
+    // every call re-enters "reenter" through the host
+    const WAT_REENTER: &str = r#"
+        (module
+            (import "env" "host_reenter" (func $host_reenter (param i32) (result i32)))
+            (memory (export "memory") 1)
+            (func (export "reenter") (param i32) (result i32)
+                local.get 0
+                call $host_reenter))
+    "#;
+
+    // returns whatever the host wrote through VmData::memory_write
+    const WAT_HOST_WRITE: &str = r#"
+        (module
+            (import "env" "host_write" (func $host_write (result i32)))
+            (memory (export "memory") 1)
+            (func (export "mem_alloc") (param i32) (result i32) i32.const 1024)
+            (func (export "echo") (result i32)
+                call $host_write))
+    "#;
+
+    // WAT_HOST_WRITE with the allocator exported as "__alloc"
+    const WAT_HOST_WRITE_ALLOC: &str = r#"
+        (module
+            (import "env" "host_write" (func $host_write (result i32)))
+            (memory (export "memory") 1)
+            (func (export "__alloc") (param i32) (result i32) i32.const 1024)
+            (func (export "echo") (result i32)
+                call $host_write))
+    "#;
+
+    // input [ len = 4 ][ 0x01 0x02 0x03 0x00 ] at ptr 64, reversed = [ ProgramCode::Ok ][ 3 2 1 ]
+    const WAT_HOST_REVERSE: &str = r#"
+        (module
+            (import "env" "reverse" (func $reverse (param i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 64) "\04\00\00\00\01\02\03\00")
+            (func (export "mem_alloc") (param i32) (result i32) i32.const 1024)
+            (func (export "run") (result i32)
+                i32.const 64
+                call $reverse))
+    "#;
+
+    // 2 of each : imports, exports, functions, globals, memory pages ( max )
+    const WAT_LIMITS: &str = r#"
+        (module
+            (import "env" "a" (func))
+            (import "env" "g" (global i32))
+            (memory (export "memory") 1 2)
+            (global i32 (i32.const 0))
+            (func (export "f")))
+    "#;
+
+    // `start` bumps the counter at instantiation
+    const WAT_START: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (global $counter (export "counter") (mut i32) (i32.const 0))
+            (func $init
+                i32.const 1
+                global.set $counter)
+            (start $init))
+    "#;
+
+    const WAT_IMPORT_MANY: &str = r#"
+        (module
+            (import "env" "charlie" (func))
+            (import "env" "alpha" (func))
+            (import "env" "delta" (func))
+            (import "env" "bravo" (func))
+            (memory (export "memory") 1))
+    "#;
+
+    // grows memory by `n` pages
+    const WAT_GROW: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (data (i32.const 16) "\01\00\00\00\00")
+            (func (export "grow") (param $n i32) (result i32)
+                local.get $n
+                memory.grow
+                drop
+                i32.const 16)
+            (func (export "grow_trap") (param $n i32) (result i32)
+                local.get $n
+                memory.grow
+                drop
+                unreachable))
+    "#;
+
+    // returns the input ptr as the program result
+    const WAT_ECHO: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "mem_alloc") (param i32) (result i32) i32.const 1024)
+            (func (export "echo") (param $ptr i32) (result i32)
+                local.get $ptr))
+    "#;
+
+    // WAT_ECHO with an allocator looping 1000 times before returning
+    const WAT_ECHO_BURN: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "mem_alloc") (param i32) (result i32) (local $n i32)
+                i32.const 1000
+                local.set $n
+                (block $done
+                    (loop $next
+                        local.get $n
+                        i32.eqz
+                        br_if $done
+                        local.get $n
+                        i32.const 1
+                        i32.sub
+                        local.set $n
+                        br $next))
+                i32.const 1024)
+            (func (export "echo") (param $ptr i32) (result i32)
+                local.get $ptr))
+    "#;
+
+    // echo : input [ len ][ data ] at ptr is rewritten in place as [ len + 1 ][ Ok ][ data ]
+    // one byte lower ( the Ok byte overwrites the input len's high byte )
+    // reject : [ len = 2 ][ ProgramCode::BorshDecodeInvalidArg ][ 0xFF ]
+    const WAT_BORSH_ECHO: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (data (i32.const 16) "\02\00\00\00\09\FF")
+            (func (export "mem_alloc") (param i32) (result i32) i32.const 1024)
+            (func (export "echo") (param $ptr i32) (result i32)
+                local.get $ptr
+                i32.const 1
+                i32.sub
+                local.get $ptr
+                i32.load
+                i32.const 1
+                i32.add
+                i32.store
+                local.get $ptr
+                i32.const 3
+                i32.add
+                i32.const 0
+                i32.store8
+                local.get $ptr
+                i32.const 1
+                i32.sub)
+            (func (export "reject") (param i32) (result i32)
+                i32.const 16))
+    "#;
+
+    // store( v ) : memory[2053] = v, load : program result [ len = 5 ][ Ok ][ v ] at ptr 2048
+    const WAT_STORE_LOAD: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (data (i32.const 2048) "\05\00\00\00\00")
+            (func (export "store") (param $v i32)
+                i32.const 2053
+                local.get $v
+                i32.store)
+            (func (export "load") (result i32)
+                i32.const 2048))
+    "#;
+
+    // host_charge( amount ) once, or in a loop until gas runs out
+    const WAT_HOST_CHARGE: &str = r#"
+        (module
+            (import "env" "host_charge" (func $host_charge (param i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 16) "\01\00\00\00\00")
+            (func (export "charge_once") (result i32)
+                i32.const 5000
+                call $host_charge
+                i32.const 16)
+            (func (export "charge_loop")
+                (loop
+                    i32.const 1000
+                    call $host_charge
+                    br 0)))
+    "#;
+
+    // host fn, direct & table calls ( fn indexes shifted by the Interrupt import ), 212 written
+    // as the program data at ptr 16
+    const WAT_INTERRUPT: &str = r#"
+        (module
+            (import "env" "host_add" (func $host_add (param i32) (result i32)))
+            (type $unary (func (param i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 16) "\05\00\00\00\00")
+            (table 2 funcref)
+            (elem (i32.const 0) $double $host_add)
+            (func $double (param i32) (result i32)
+                local.get 0
+                i32.const 2
+                i32.mul)
+            (func (export "calls") (result i32)
+                i32.const 21
+                i32.const 3
+                call $double
+                i32.const 0
+                call_indirect (type $unary)
+                i32.const 1
+                call_indirect (type $unary)
+                call $host_add
+                i32.store
+                i32.const 16)
+            (func (export "spin") (loop br 0)))
+    "#;
+
+    // bump allocator with a single-slot free list ( fixed size buffers only )
+    const WAT_ALLOC_FREE: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (global $bump (mut i32) (i32.const 1024))
+            (global $free (mut i32) (i32.const 0))
+            (func (export "mem_alloc") (param $size i32) (result i32) (local $ptr i32)
+                global.get $free
+                if (result i32)
+                    global.get $free
+                    i32.const 0
+                    global.set $free
+                else
+                    global.get $bump
+                    local.set $ptr
+                    global.get $bump
+                    local.get $size
+                    i32.add
+                    global.set $bump
+                    (block $done
+                        (loop $grow
+                            global.get $bump
+                            memory.size
+                            i32.const 16
+                            i32.shl
+                            i32.le_u
+                            br_if $done
+                            i32.const 1
+                            memory.grow
+                            drop
+                            br $grow))
+                    local.get $ptr
+                end)
+            (func (export "mem_free") (param $ptr i32)
+                local.get $ptr
+                global.set $free))
+    "#;
+
+    // len ( 8byte, le ) + data
+    #[derive(Debug)]
+    struct Len64Codec;
+
+    impl MemCodec for Len64Codec {
+        fn header_size(&self) -> usize {
+            8
+        }
+
+        fn encode(&self, bytes: &[u8]) -> Vec<u8> {
+            let mut buffer = Vec::with_capacity(8 + bytes.len());
+            buffer.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+            buffer.extend_from_slice(bytes);
+            buffer
+        }
+
+        fn decode_len(&self, len: &[u8]) -> usize {
+            match <[u8; 8]>::try_from(len) {
+                Ok(len) => u64::from_le_bytes(len) as usize,
+                Err(_) => 0,
+            }
+        }
+    }
+
+    // counter += 1, memory[2048] = counter
+    const WAT_COUNTER: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (global $counter (export "counter") (mut i32) (i32.const 0))
+            (func (export "mem_alloc") (param i32) (result i32) i32.const 4096)
+            (func (export "bump")
+                global.get $counter
+                i32.const 1
+                i32.add
+                global.set $counter
+                i32.const 2048
+                global.get $counter
+                i32.store))
+    "#;
+
+    // partial output [ len = 3 ][ 0x01 0x02 0x03 ] at ptr 64, published before spinning
+    const WAT_PARTIAL: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (data (i32.const 64) "\03\00\00\00\01\02\03")
+            (global (export "partial_ret_ptr") (mut i32) (i32.const 0))
+            (func (export "spin_partial")
+                i32.const 64
+                global.set 0
+                (loop br 0)))
+    "#;
+
     #[test]
     fn run_basic() {
         let wasm_binary = load_file(FILE_PATH_WASM);
@@ -502,6 +2163,2275 @@ mod tests {
         println!("result : {:?}", result);
     }
 
+    #[test]
+    fn memory_snapshot_reset_restore() {
+        let opcode = load_file(FILE_PATH_WASM);
+        let offset = 1024;
+        let value = [7, 8, 9, 10];
+
+        // init
+        let launcher = VMLauncher::new(&opcode, false, false, None);
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        let mut launcher = launcher.unwrap();
+
+        // write & snapshot
+        launcher
+            .with_initial_memory_at(offset as u64, &value)
+            .unwrap();
+        let snapshot = launcher.snapshot_memory().unwrap();
+        assert_eq!(snapshot[offset..offset + 4], value);
+
+        // reset - value is gone
+        launcher.reset().unwrap();
+        let fresh = launcher.snapshot_memory().unwrap();
+        assert_ne!(fresh[offset..offset + 4], value);
+
+        // restore - value persists
+        launcher.with_initial_memory(&snapshot).unwrap();
+        let restored = launcher.snapshot_memory().unwrap();
+        assert_eq!(restored[offset..offset + 4], value);
+
+        // run - restored instance still works
+        let vm_ret = launcher.run(0, 0, "example");
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::Ok),
+            "{:?}",
+            vm_ret
+        );
+
+        // write - out of bounds
+        let mem_size = launcher.snapshot_memory().unwrap().len();
+        let ret = launcher.with_initial_memory_at(mem_size as u64, &value);
+        assert!(
+            matches!(
+                ret,
+                Err(EmVmError::MemoryWriteFail(
+                    EmMemError::MemoryWriteOutOfBounds(_)
+                ))
+            ),
+            "{:?}",
+            ret
+        );
+    }
+
+    #[test]
+    fn state_restored_across_runs() {
+        let offset = 2048;
+
+        // init
+        let launcher = VMLauncher::new(WAT_COUNTER.as_bytes(), false, false, None);
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        let mut launcher = launcher.unwrap();
+
+        // run - mutate global & memory, then snapshot
+        launcher.run(0, 0, "bump");
+        let memory = launcher.snapshot_memory().unwrap();
+        let globals = launcher.snapshot_globals();
+        assert_eq!(globals.len(), 1);
+        assert_eq!(globals[0].0, "counter");
+        assert_eq!(globals[0].1.i32(), Some(1));
+
+        // reset - state is gone
+        launcher.reset().unwrap();
+        assert_eq!(launcher.snapshot_globals()[0].1.i32(), Some(0));
+
+        // restore
+        launcher.restore_memory(&memory).unwrap();
+        launcher.restore_globals(&globals).unwrap();
+        let restored = launcher.snapshot_memory().unwrap();
+        assert_eq!(restored[offset..offset + 4], 1u32.to_le_bytes());
+
+        // run - continues from the restored state
+        launcher.run(0, 0, "bump");
+        assert_eq!(launcher.snapshot_globals()[0].1.i32(), Some(2));
+        let memory = launcher.snapshot_memory().unwrap();
+        assert_eq!(memory[offset..offset + 4], 2u32.to_le_bytes());
+    }
+
+    #[test]
+    fn reset_drops_old_store() {
+        // init - external updated by a host fn
+        let host_charge: ImportedFn<u32> = Box::new(|mut env, args| {
+            *env.data_mut().1.as_mut().unwrap() += args[0].unwrap_i32() as u32;
+            Ok(vec![])
+        });
+        let mut imported_fn = HashMap::new();
+        imported_fn.insert(
+            "host_charge".to_string(),
+            (host_charge, FunctionType::new(vec![Type::I32], vec![])),
+        );
+        let mut launcher = VMLauncher::new_with_external(
+            WAT_HOST_CHARGE.as_bytes(),
+            false,
+            true,
+            7u32,
+            imported_fn,
+            None,
+        )
+        .unwrap();
+        let globals = launcher.store.objects_mut().iter_globals().count();
+
+        // reset - store objects don't pile up, host fns & external survive
+        for _ in 0..10 {
+            launcher.reset().unwrap();
+            assert_eq!(launcher.store.objects_mut().iter_globals().count(), globals);
+
+            let vm_ret = launcher.run(1, 100000, "charge_once");
+            assert!(vm_ret.error.is_none(), "{:?}", vm_ret);
+        }
+        assert_eq!(launcher.shutdown(), 50007);
+    }
+
+    #[test]
+    fn restore_memory_grows() {
+        let opcode = WAT_COUNTER.as_bytes();
+
+        // init
+        let mut launcher = VMLauncher::new(opcode, false, false, None).unwrap();
+        let mem_size = launcher.snapshot_memory().unwrap().len();
+
+        // restore - snapshot larger than the current memory
+        let mut snapshot = vec![0; mem_size * 2];
+        snapshot[mem_size + 1] = 0xAB;
+        launcher.restore_memory(&snapshot).unwrap();
+
+        let restored = launcher.snapshot_memory().unwrap();
+        assert_eq!(restored.len(), mem_size * 2);
+        assert_eq!(restored[mem_size + 1], 0xAB);
+    }
+
+    #[test]
+    fn gas_refund_out_of_gas() {
+        let gas_limit = 1000;
+
+        // init
+        let launcher = VMLauncher::new(WAT_SPIN.as_bytes(), false, true, None);
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+
+        // run - all gas is charged, nothing refunded
+        let vm_ret = launcher.unwrap().run(1, gas_limit, "spin");
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::OutOfGas),
+            "{:?}",
+            vm_ret
+        );
+        assert_eq!(vm_ret.gas_used, gas_limit);
+        assert_eq!(vm_ret.gas_refund, 0);
+    }
+
+    #[test]
+    fn program_code_http_status() {
+        assert_eq!(ProgramCode::Ok.http_status(), 200);
+        assert_eq!(ProgramCode::OutOfGas.http_status(), 402);
+        assert_eq!(ProgramCode::FnInvalidEntryPoint.http_status(), 404);
+        assert_eq!(ProgramCode::FnInvalidArgs.http_status(), 400);
+        assert_eq!(ProgramCode::VmError.http_status(), 500);
+        assert_eq!(ProgramCode::UnknownError.http_status(), 500);
+    }
+
+    #[test]
+    fn out_of_gas_partial_data() {
+        let gas_limit = 1000;
+
+        // run - off ( default ), partial output dropped
+        let launcher = VMLauncher::new(WAT_PARTIAL.as_bytes(), false, true, None);
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        let vm_ret = launcher.unwrap().run(1, gas_limit, "spin_partial");
+        assert!(matches!(vm_ret.program_code, ProgramCode::OutOfGas));
+        assert!(vm_ret.program_data.is_empty());
+
+        // run - on, partial output kept, all gas still charged
+        let config = VmConfig {
+            out_of_gas_partial_data: true,
+            ..VmConfig::default()
+        };
+        let launcher =
+            VMLauncher::new_with_config(WAT_PARTIAL.as_bytes(), false, true, None, config.clone());
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        let vm_ret = launcher.unwrap().run(1, gas_limit, "spin_partial");
+        assert!(matches!(vm_ret.program_code, ProgramCode::OutOfGas));
+        assert_eq!(vm_ret.error, Some(EmVmError::FunctionCallOutOfGas));
+        assert_eq!(vm_ret.program_data, vec![1, 2, 3]);
+        assert_eq!(vm_ret.gas_used, gas_limit);
+
+        // run - on, no partial ret ptr exported
+        let launcher = VMLauncher::new_with_config(WAT_SPIN.as_bytes(), false, true, None, config);
+        let vm_ret = launcher.unwrap().run(1, gas_limit, "spin");
+        assert!(matches!(vm_ret.program_code, ProgramCode::OutOfGas));
+        assert!(vm_ret.program_data.is_empty());
+    }
+
+    #[test]
+    fn gas_refund_partial_use() {
+        let opcode = load_file(FILE_PATH_WASM);
+        let gas_limit = 10000000;
+
+        // init
+        let launcher = VMLauncher::new(&opcode, false, true, Some(custom_gas_consumption()));
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+
+        // run - unused gas is refunded
+        let vm_ret = launcher.unwrap().run(1, gas_limit, "example");
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::Ok),
+            "{:?}",
+            vm_ret
+        );
+        assert!(vm_ret.gas_used > 0);
+        assert!(vm_ret.gas_refund > 0);
+        assert_eq!(vm_ret.gas_used + vm_ret.gas_refund, gas_limit);
+    }
+
+    #[test]
+    fn gas_refund_no_priority() {
+        let opcode = load_file(FILE_PATH_WASM);
+        let gas_limit = 5000;
+
+        // init
+        let launcher = VMLauncher::new(&opcode, false, false, None);
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+
+        // run - nothing metered, whole limit refunded
+        let vm_ret = launcher.unwrap().run(0, gas_limit, "example");
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::Ok),
+            "{:?}",
+            vm_ret
+        );
+        assert_eq!(vm_ret.gas_used, 0);
+        assert_eq!(vm_ret.gas_refund, gas_limit);
+    }
+
+    #[test]
+    fn export_verified_module() {
+        let opcode = load_file(FILE_PATH_WASM);
+
+        // init
+        let launcher = VMLauncher::new(&opcode, false, true, Some(custom_gas_consumption()));
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        let mut launcher = launcher.unwrap();
+
+        // export - verified
+        let module_bytes = launcher.export_verified_module();
+        assert!(module_bytes.is_ok(), "{:?}", module_bytes.err());
+
+        // import - verified bytes
+        let module_bytes = module_bytes.unwrap();
+        let imported = VMLauncher::new(&module_bytes, true, true, Some(custom_gas_consumption()));
+        assert!(imported.is_ok(), "{:?}", imported.err());
+        let vm_ret = imported.unwrap().run(1, 10000000, "example");
+        assert!(vm_ret.error.is_none(), "{:?}", vm_ret);
+
+        // verify - a corrupted byte in the body is rejected ( middle & last )
+        for index in [module_bytes.len() / 2, module_bytes.len() - 1] {
+            let mut corrupted = module_bytes.clone();
+            corrupted[index] ^= 0xFF;
+            let ret = launcher.verify_module_opcode(&corrupted);
+            assert_eq!(
+                ret,
+                Err(EmVmError::ExportModuleVerifyFail(
+                    ModuleError::EncodedModuleChecksumInvalid
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn ret_program_ptr_types() {
+        // init
+        let launcher = VMLauncher::new(WAT_RET_PTR.as_bytes(), false, false, None);
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        let mut launcher = launcher.unwrap();
+
+        // run - i64 ptr & ptr as second value
+        for fn_name in ["ret_i64", "ret_multi"] {
+            let vm_ret = launcher.run(0, 0, fn_name);
+            assert!(
+                matches!(vm_ret.program_code, ProgramCode::Ok),
+                "{:?}",
+                vm_ret
+            );
+            assert_eq!(vm_ret.program_data, vec![0xAA, 0xBB]);
+        }
+
+        // run - any other shape
+        for (fn_name, types) in [
+            ("ret_triple", "[I32, I32, I32]"),
+            ("ret_float_code", "[F32, I32]"),
+        ] {
+            let vm_ret = launcher.run(0, 0, fn_name);
+            assert_eq!(vm_ret.program_code, ProgramCode::VmError, "{:?}", vm_ret);
+            assert_eq!(
+                vm_ret.error,
+                Some(EmVmError::RetProgramShapeInvalid(types.to_string()))
+            );
+        }
+
+        // run - negative ptr
+        for fn_name in ["ret_negative", "ret_negative_i64"] {
+            let vm_ret = launcher.run(0, 0, fn_name);
+            assert!(
+                matches!(vm_ret.program_code, ProgramCode::UndefinedErrPtr),
+                "{:?}",
+                vm_ret
+            );
+            assert!(
+                matches!(vm_ret.error, Some(EmVmError::RetProgramPtrInvalid(_))),
+                "{:?}",
+                vm_ret
+            );
+        }
+    }
+
+    #[test]
+    fn ret_program_ptr_out_of_bounds() {
+        let mut launcher = VMLauncher::new(WAT_RET_PTR.as_bytes(), false, false, None).unwrap();
+
+        // run - ptr at the end of a 1 page memory
+        let vm_ret = launcher.run(0, 0, "ret_out_of_bounds");
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::UndefinedErrPtr),
+            "{:?}",
+            vm_ret
+        );
+        assert_eq!(
+            vm_ret.error,
+            Some(EmVmError::RetProgramPtrInvalid(
+                "ptr out of bounds : 65536 ( memory size 65536 )".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn host_fn_bytes_reverse() {
+        // init - byte-in / byte-out host fn
+        let (name, entry) = host_fn_bytes::<u32, _>("reverse", |_env, input| {
+            input.iter().rev().copied().collect()
+        });
+        let mut imported_fn = HashMap::new();
+        imported_fn.insert(name, entry);
+        let launcher = VMLauncher::new_with_external(
+            WAT_HOST_REVERSE.as_bytes(),
+            false,
+            false,
+            0,
+            imported_fn,
+            None,
+        );
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+
+        // run
+        let vm_ret = launcher.unwrap().run(0, 0, "run");
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::Ok),
+            "{:?}",
+            vm_ret
+        );
+        assert_eq!(vm_ret.program_data, vec![3, 2, 1]);
+
+        // run - the output allocation is a guest re-entry, guarded by max_call_depth
+        let (name, entry) = host_fn_bytes::<u32, _>("reverse", |_env, input| {
+            input.iter().rev().copied().collect()
+        });
+        let mut imported_fn = HashMap::new();
+        imported_fn.insert(name, entry);
+        let config = VmConfig {
+            max_call_depth: Some(0),
+            ..VmConfig::default()
+        };
+        let mut launcher = VMLauncher::new_with_external_config(
+            WAT_HOST_REVERSE.as_bytes(),
+            false,
+            false,
+            0,
+            imported_fn,
+            None,
+            config,
+        )
+        .unwrap();
+        let vm_ret = launcher.run(0, 0, "run");
+        assert_eq!(
+            vm_ret.program_code,
+            ProgramCode::CallDepthExceeded,
+            "{:?}",
+            vm_ret
+        );
+    }
+
+    #[test]
+    fn ret_program_empty_data() {
+        let mut launcher = VMLauncher::new(WAT_RET_PTR.as_bytes(), false, false, None).unwrap();
+
+        // run - Ok code byte only
+        let vm_ret = launcher.run(0, 0, "ret_ok_empty");
+        assert_eq!(vm_ret.program_code, ProgramCode::Ok);
+        assert!(vm_ret.error.is_none());
+        assert!(vm_ret.program_data.is_empty());
+
+        // run - no code byte
+        let vm_ret = launcher.run(0, 0, "ret_empty");
+        assert_eq!(vm_ret.program_code, ProgramCode::UndefinedErrPtr);
+        assert_eq!(
+            vm_ret.error,
+            Some(EmVmError::RetProgramPtrInvalid(
+                "program result empty : 48".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn default_schedule_prices_floats() {
+        let opcode = WAT_FLOAT.as_bytes();
+        let run_float = |gas_consumption: GasConsumptionFn| -> u64 {
+            let launcher = VMLauncher::new(opcode, false, true, Some(gas_consumption));
+            assert!(launcher.is_ok(), "{:?}", launcher.err());
+
+            let vm_ret = launcher.unwrap().run(1, 10000000, "float_math");
+            assert!(vm_ret.error.is_none(), "{:?}", vm_ret);
+            vm_ret.gas_used
+        };
+
+        // run - default schedule vs all-ones fallback
+        let gas_default = run_float(GasMetering::default_schedule());
+        let gas_ones = run_float(Arc::new(|_: &Operator| -> u64 { 1 }));
+        assert!(gas_default > gas_ones, "{} <= {}", gas_default, gas_ones);
+
+        // run - overridden float costs
+        let schedule = GasSchedule {
+            float: 1,
+            float_div: 1,
+            ..GasSchedule::default()
+        };
+        let gas_override = run_float(schedule.into_consumption());
+        assert!(
+            gas_override < gas_default,
+            "{} >= {}",
+            gas_override,
+            gas_default
+        );
+    }
+
+    #[test]
+    fn run_with_fuel() {
+        let opcode = WAT_WORK.as_bytes();
+        let args = [Value::I32(10)];
+
+        // init
+        let launcher = VMLauncher::new(opcode, false, true, Some(custom_gas_consumption()));
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        let mut launcher = launcher.unwrap();
+
+        // run - measure consumption
+        let vm_ret = launcher.run_with_fuel(10000000, "work", &args);
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::Ok),
+            "{:?}",
+            vm_ret
+        );
+        let fuel = vm_ret.gas_used;
+
+        // run - more work costs more fuel
+        let vm_ret = launcher.run_with_fuel(10000000, "work", &[Value::I32(20)]);
+        assert!(vm_ret.gas_used > fuel);
+
+        // run - exact fuel succeeds with the exact consumption
+        let vm_ret = launcher.run_with_fuel(fuel, "work", &args);
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::Ok),
+            "{:?}",
+            vm_ret
+        );
+        assert_eq!(vm_ret.gas_used, fuel);
+        assert_eq!(vm_ret.gas_refund, 0);
+
+        // run - not enough fuel
+        let vm_ret = launcher.run_with_fuel(fuel - 1, "work", &args);
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::OutOfGas),
+            "{:?}",
+            vm_ret
+        );
+        assert_eq!(vm_ret.gas_used, fuel - 1);
+    }
+
+    #[test]
+    fn validate_features() {
+        let opcode_float = wat2wasm(WAT_FLOAT.as_bytes()).unwrap();
+        let opcode_int = wat2wasm(WAT_WORK.as_bytes()).unwrap();
+        let no_floats = FeaturePolicy {
+            floats: false,
+            ..FeaturePolicy::allow_all()
+        };
+
+        // validate - floats rejected
+        let ret = VmModule::validate_features(&opcode_float, no_floats.clone());
+        assert!(
+            matches!(
+                ret,
+                Err(ModuleError::FeatureNotAllowed(WasmFeature::Floats, _))
+            ),
+            "{:?}",
+            ret
+        );
+
+        // validate - floats allowed
+        let ret = VmModule::validate_features(&opcode_float, FeaturePolicy::allow_all());
+        assert!(ret.is_ok(), "{:?}", ret);
+
+        // validate - integer only module passes every policy
+        let ret = VmModule::validate_features(&opcode_int, no_floats);
+        assert!(ret.is_ok(), "{:?}", ret);
+        let ret = VmModule::validate_features(&opcode_int, FeaturePolicy::deterministic());
+        assert!(ret.is_ok(), "{:?}", ret);
+
+        // validate - malformed wasm
+        let ret = VmModule::validate_features(&[0, 1, 2, 3], FeaturePolicy::allow_all());
+        assert!(
+            matches!(ret, Err(ModuleError::ValidateFail(_))),
+            "{:?}",
+            ret
+        );
+    }
+
+    #[test]
+    fn module_policy_features() {
+        let config = VmConfig {
+            module_policy: ModulePolicy {
+                features: FeaturePolicy::deterministic(),
+                ..ModulePolicy::default()
+            },
+            ..VmConfig::default()
+        };
+
+        // init - floats rejected on the compile path
+        let launcher =
+            VMLauncher::new_with_config(WAT_FLOAT.as_bytes(), false, false, None, config.clone());
+        assert!(
+            matches!(
+                launcher.err(),
+                Some(EmVmError::NewModuleInitBinaryFail(
+                    ModuleError::FeatureNotAllowed(WasmFeature::Floats, _)
+                ))
+            ),
+            "feature policy not applied"
+        );
+
+        // init - integer only module
+        let launcher = VMLauncher::new_with_config(WAT_WORK.as_bytes(), false, false, None, config);
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+    }
+
+    #[test]
+    fn nan_canonicalized_in_metered_mode() {
+        let opcode = WAT_NAN.as_bytes();
+        let canonical_nan = 0x7FF8_0000_0000_0000u64.to_le_bytes().to_vec();
+        let run_nan = || -> Vec<u8> {
+            let launcher = VMLauncher::new(opcode, false, true, None);
+            assert!(launcher.is_ok(), "{:?}", launcher.err());
+
+            let vm_ret = launcher.unwrap().run(1, 10000000, "nan");
+            assert!(
+                matches!(vm_ret.program_code, ProgramCode::Ok),
+                "{:?}",
+                vm_ret
+            );
+            vm_ret.program_data
+        };
+
+        // run - identical canonical bits across launchers
+        let first = run_nan();
+        let second = run_nan();
+        assert_eq!(first, second);
+        assert_eq!(first, canonical_nan);
+
+        // run - explicitly enabled without metering
+        let config = VmConfig {
+            canonicalize_nans: Some(true),
+            ..VmConfig::default()
+        };
+        let launcher = VMLauncher::new_with_config(opcode, false, false, None, config);
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        let vm_ret = launcher.unwrap().run(0, 0, "nan");
+        assert_eq!(vm_ret.program_data, canonical_nan);
+    }
+
+    #[test]
+    fn memory_size_bytes() {
+        let opcode = load_file(FILE_PATH_WASM);
+        let page_size = 64 * 1024;
+
+        // init
+        let launcher = VMLauncher::new(&opcode, false, false, None);
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        let launcher = launcher.unwrap();
+
+        // load - size in whole pages
+        let size = launcher.memory_size_bytes();
+        assert!(size.is_ok(), "{:?}", size.err());
+        let size = size.unwrap();
+        assert!(size > 0);
+        assert_eq!(size % page_size, 0);
+    }
+
+    #[test]
+    fn import_signature_mismatch() {
+        let opcode = WAT_IMPORT_ADD.as_bytes();
+        let host_add: ImportedFn<u32> = Box::new(|_env, args| Ok(vec![args[0].clone()]));
+        let wrong_type = FunctionType::new(vec![Type::I32], vec![Type::I32]);
+
+        // init - host fn with the wrong arity
+        let mut imported_fn = HashMap::new();
+        imported_fn.insert("host_add".to_string(), (host_add, wrong_type.clone()));
+        let launcher = VMLauncher::new_with_external(opcode, false, false, 0, imported_fn, None);
+
+        match launcher.err() {
+            Some(EmVmError::ImportSignatureMismatch {
+                name,
+                expected,
+                got,
+            }) => {
+                let guest_type = FunctionType::new(vec![Type::I32, Type::I32], vec![Type::I32]);
+                assert_eq!(name, "host_add");
+                assert_eq!(expected, guest_type.to_string());
+                assert_eq!(got, wrong_type.to_string());
+            }
+            e => panic!("unexpected : {:?}", e),
+        }
+    }
+
+    #[test]
+    fn memory_len_prefix_le() {
+        // check - byte order is pinned ( guest ABI )
+        assert_eq!(::memory::Memory::decode_len(&[1, 0, 0, 0]), 1);
+        assert_eq!(::memory::Memory::decode_len(&[0, 1, 0, 0]), 256);
+        assert_eq!(::memory::Memory::decode_len(&[0, 0, 1, 0]), 65536);
+        assert_eq!(
+            ::memory::Memory::decode_len(&u32::MAX.to_le_bytes()),
+            u32::MAX as usize
+        );
+        assert_eq!(::memory::Memory::decode_len(&[1, 0, 0]), 0);
+
+        // check - encode / decode round trip
+        for len in [0usize, 1, 255, 256, 65535, 65536] {
+            let bytes: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let encoded = ::memory::Memory::encode(&bytes);
+            assert_eq!(encoded[0..4], (len as u32).to_le_bytes(), "len {}", len);
+            assert_eq!(::memory::Memory::decode_len(&encoded[0..4]), len);
+            assert_eq!(::memory::Memory::decode(&encoded), bytes);
+        }
+
+        // check - guest-side Memory::new uses the same 4byte header
+        let ptr = ::memory::Memory::new(3, &[7, 8, 9]);
+        assert_eq!(::memory::Memory::export_length(ptr), 3);
+        assert_eq!(::memory::Memory::decode_ptr(ptr), vec![7, 8, 9]);
+    }
+
+    #[test]
+    fn require_mem_exports() {
+        // WAT_SPIN exports memory but no mem_alloc
+        let opcode = WAT_SPIN.as_bytes();
+
+        // init - check off ( default )
+        let launcher = VMLauncher::new_with_config(opcode, false, false, None, VmConfig::default());
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+
+        // init - check on
+        let config = VmConfig {
+            require_mem_exports: true,
+            ..VmConfig::default()
+        };
+        let launcher = VMLauncher::new_with_config(opcode, false, false, None, config.clone());
+        assert_eq!(
+            launcher.err(),
+            Some(EmVmError::MissingRequiredExport("mem_alloc".to_string()))
+        );
+
+        // init - check on, both exports present
+        let launcher =
+            VMLauncher::new_with_config(WAT_COUNTER.as_bytes(), false, false, None, config);
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+    }
+
+    #[test]
+    fn mem_alloc_fn_name() {
+        let opcode = WAT_HOST_WRITE_ALLOC.as_bytes();
+        let config = VmConfig {
+            mem_alloc_fn: Some("__alloc".to_string()),
+            require_mem_exports: true,
+            ..VmConfig::default()
+        };
+
+        // init - host fn writing an Ok result
+        let host_write: ImportedFn<u32> = Box::new(|mut env, _args| {
+            let (data, mut store) = env.data_and_store_mut();
+            let ptr = data
+                .0
+                .memory_write(&mut store, &[ProgramCode::Ok.to_i32() as u8, 1, 2])
+                .map_err(|e| RuntimeError::new(format!("{:?}", e)))?;
+            Ok(vec![Value::I32(ptr as i32)])
+        });
+        let fn_type = FunctionType::new(vec![], vec![Type::I32]);
+        let mut imported_fn = HashMap::new();
+        imported_fn.insert("host_write".to_string(), (host_write, fn_type));
+        let launcher = VMLauncher::new_with_external_config(
+            opcode,
+            false,
+            false,
+            0,
+            imported_fn,
+            None,
+            config,
+        );
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        let mut launcher = launcher.unwrap();
+
+        // run - host write goes through "__alloc"
+        let vm_ret = launcher.run(0, 0, "echo");
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::Ok),
+            "{:?}",
+            vm_ret
+        );
+        assert_eq!(vm_ret.program_data, vec![1, 2]);
+
+        // write - missing allocator lists the available fns
+        let ret = VmMemory::mem_write_store(&mut launcher.store, &launcher.instance, &[1]);
+        match ret {
+            Err(EmMemError::MemoryAllocGetFnFail(e)) => {
+                assert!(e.contains("\"__alloc\""), "{}", e);
+                assert!(e.contains("\"echo\""), "{}", e);
+            }
+            e => panic!("unexpected : {:?}", e),
+        }
+    }
+
+    #[test]
+    fn guest_buffer_freed() {
+        let launcher = VMLauncher::new(WAT_ALLOC_FREE.as_bytes(), false, false, None);
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        let mut launcher = launcher.unwrap();
+        let mem_size = launcher.memory_size_bytes().unwrap();
+
+        // write - many 1KB buffers, each freed after use
+        let data = vec![0xAB; 1024];
+        for _ in 0..200 {
+            let ret = VmMemory::with_guest_buffer(
+                &mut launcher.store,
+                &launcher.instance,
+                &data,
+                |store, ptr| VmMemory::mem_read_store(store, &launcher.instance, ptr),
+            );
+            assert_eq!(ret.unwrap().unwrap(), data);
+        }
+        assert_eq!(launcher.memory_size_bytes().unwrap(), mem_size);
+
+        // write - without freeing, memory grows
+        for _ in 0..200 {
+            VmMemory::mem_write_store(&mut launcher.store, &launcher.instance, &data).unwrap();
+        }
+        assert!(launcher.memory_size_bytes().unwrap() > mem_size);
+
+        // free - skipped when the guest exports no mem_free
+        let mut launcher = VMLauncher::new(WAT_COUNTER.as_bytes(), false, false, None).unwrap();
+        let ret = VmMemory::mem_dealloc_store(&mut launcher.store, &launcher.instance, 4096, 4);
+        assert_eq!(ret, Ok(false));
+    }
+
+    #[test]
+    fn mem_alloc_size_overflow() {
+        // check - data + header
+        assert_eq!(VmMemory::mem_alloc_size(10, &::memory::Memory), Ok(14));
+        assert_eq!(VmMemory::mem_alloc_size(10, &Len64Codec), Ok(18));
+
+        // check - past u32 ( would have been truncated )
+        let ret = VmMemory::mem_alloc_size(u32::MAX as usize, &::memory::Memory);
+        assert!(
+            matches!(ret, Err(EmMemError::MemoryAllocSizeOverflow(_))),
+            "{:?}",
+            ret
+        );
+        let ret = VmMemory::mem_alloc_size(usize::MAX, &::memory::Memory);
+        assert!(
+            matches!(ret, Err(EmMemError::MemoryAllocSizeOverflow(_))),
+            "{:?}",
+            ret
+        );
+    }
+
+    #[test]
+    fn guest_buffer_codec_and_free_signature() {
+        let opcode = r#"
+            (module
+                (memory (export "memory") 1)
+                (global $freed (export "freed") (mut i32) (i32.const 0))
+                (func (export "__alloc") (param i32) (result i32) i32.const 1024)
+                (func (export "mem_free") (param $ptr i32) (param $size i32)
+                    local.get $size
+                    global.set $freed))
+        "#;
+        let config = VmConfig {
+            mem_codec: Some(Arc::new(Len64Codec)),
+            mem_alloc_fn: Some("__alloc".to_string()),
+            ..VmConfig::default()
+        };
+        let mut launcher =
+            VMLauncher::new_with_config(opcode.as_bytes(), false, false, None, config).unwrap();
+
+        // write - configured codec & allocator, mem_free(ptr, size) gets the allocated size
+        let data = vec![0xAB; 10];
+        let ret = launcher.with_guest_buffer(&data, |store, instance, ptr| {
+            assert_eq!(ptr, 1024);
+            VmMemory::mem_read_store_codec(store, instance, ptr, &Len64Codec)
+        });
+        assert_eq!(ret.unwrap().unwrap(), data);
+        assert_eq!(launcher.read_globals()[0].1.i32(), Some(18));
+
+        // free - unsupported signature is an error
+        let opcode = r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "mem_free") (param i64)))
+        "#;
+        let mut launcher = VMLauncher::new(opcode.as_bytes(), false, false, None).unwrap();
+        let ret = VmMemory::mem_dealloc_store(&mut launcher.store, &launcher.instance, 1024, 8);
+        assert_eq!(
+            ret,
+            Err(EmMemError::MemoryFreeSignatureInvalid(
+                "[I64] -> []".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn launcher_pool() {
+        // init - pool loading a pre-compiled module
+        let opcode = load_file(FILE_PATH_WASM);
+        let launcher = VMLauncher::new(&opcode, false, true, Some(custom_gas_consumption()));
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        let module_opcode = launcher.unwrap().get_module_opcode().unwrap();
+        let pool = Arc::new(LauncherPool::new(
+            2,
+            Box::new(move || {
+                VMLauncher::new(&module_opcode, true, true, Some(custom_gas_consumption()))
+            }),
+        ));
+
+        // run - checkout / run / return from several threads
+        let threads: Vec<_> = (0..8u8)
+            .map(|i| {
+                let pool = pool.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..4 {
+                        let mut launcher = pool.checkout().unwrap();
+                        assert!(pool.live_count() <= 2);
+
+                        // check - no marker left by a previous user
+                        let offset = launcher.memory_size_bytes().unwrap() - 1;
+                        assert_eq!(launcher.snapshot_memory().unwrap()[offset as usize], 0);
+                        launcher.with_initial_memory_at(offset, &[i + 1]).unwrap();
+
+                        let vm_ret = launcher.run(1, 10000000, "example");
+                        assert!(
+                            matches!(vm_ret.program_code, ProgramCode::Ok),
+                            "{:?}",
+                            vm_ret
+                        );
+                        assert_eq!(vm_ret.program_data, vec![100, 250, 0, 1, 2]);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // check - every live launcher was returned
+        assert!(pool.live_count() <= 2);
+        assert_eq!(pool.idle_count(), pool.live_count());
+    }
+
+    #[test]
+    fn pool_checkin_while_panicking() {
+        let opcode = WAT_COUNTER.as_bytes().to_vec();
+        let pool = Arc::new(LauncherPool::new(
+            1,
+            Box::new(move || VMLauncher::new(&opcode, false, false, None)),
+        ));
+
+        // run - checked out launcher is returned by `Drop` while unwinding
+        let thread_pool = pool.clone();
+        let ret = std::thread::spawn(move || {
+            let mut launcher = thread_pool.checkout().unwrap();
+            launcher.run(0, 0, "bump");
+            panic!("caller panic");
+        })
+        .join();
+        assert!(ret.is_err());
+        assert_eq!(pool.idle_count(), 1);
+
+        // checkout - reset launcher is reused
+        let mut launcher = pool.checkout().unwrap();
+        assert_eq!(launcher.snapshot_globals()[0].1.i32(), Some(0));
+        assert_eq!(pool.live_count(), 1);
+    }
+
+    #[test]
+    fn host_fn_charge_gas() {
+        let opcode = WAT_HOST_CHARGE.as_bytes();
+        let host_charge: ImportedFn<u32> = Box::new(|mut env, args| {
+            let amount = args[0].unwrap_i32() as u64;
+            VmData::charge_gas(&mut env, amount)?;
+            Ok(vec![])
+        });
+        let fn_type = FunctionType::new(vec![Type::I32], vec![]);
+        let mut imported_fn = HashMap::new();
+        imported_fn.insert("host_charge".to_string(), (host_charge, fn_type));
+        let launcher = VMLauncher::new_with_external(opcode, false, true, 0, imported_fn, None);
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        let mut launcher = launcher.unwrap();
+
+        // run - host charge is included in gas_used
+        let vm_ret = launcher.run_with_fuel(100000, "charge_once", &[]);
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::Ok),
+            "{:?}",
+            vm_ret
+        );
+        assert!(vm_ret.gas_used >= 5000, "{:?}", vm_ret);
+
+        // run - host charges exhaust the gas
+        let vm_ret = launcher.run_with_fuel(100000, "charge_loop", &[]);
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::OutOfGas),
+            "{:?}",
+            vm_ret
+        );
+        assert_eq!(vm_ret.gas_used, 100000);
+    }
+
+    #[test]
+    fn run_with_input() {
+        let launcher = VMLauncher::new(WAT_ECHO.as_bytes(), false, true, None);
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        let mut launcher = launcher.unwrap();
+
+        // run - input [ ProgramCode::Ok ][ data ] comes back as the program result
+        let input = [ProgramCode::Ok.to_i32() as u8, 1, 2, 3];
+        let vm_ret = launcher.run_with_input(1, 10000000, "echo", &input);
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::Ok),
+            "{:?}",
+            vm_ret
+        );
+        assert_eq!(vm_ret.program_data, vec![1, 2, 3]);
+        let echo_gas_used = vm_ret.gas_used;
+
+        // run - the allocator's gas is charged to the run
+        let mut launcher = VMLauncher::new(WAT_ECHO_BURN.as_bytes(), false, true, None).unwrap();
+        let vm_ret = launcher.run_with_input(1, 10000000, "echo", &input);
+        assert_eq!(vm_ret.program_code, ProgramCode::Ok, "{:?}", vm_ret);
+        assert!(vm_ret.gas_used > echo_gas_used + 1000, "{:?}", vm_ret);
+
+        // run - allocator out of gas
+        let vm_ret = launcher.run_with_input(1, 100, "echo", &input);
+        assert_eq!(vm_ret.program_code, ProgramCode::OutOfGas, "{:?}", vm_ret);
+        assert_eq!(vm_ret.error, Some(EmVmError::FunctionCallOutOfGas));
+        assert_eq!(vm_ret.gas_used, 100);
+
+        // run - entry point without a ptr param
+        let mut launcher = VMLauncher::new(WAT_COUNTER.as_bytes(), false, true, None).unwrap();
+        let vm_ret = launcher.run_with_input(1, 10000000, "bump", &input);
+        assert!(vm_ret.error.is_some(), "{:?}", vm_ret);
+    }
+
+    #[test]
+    fn u256_checked_math() {
+        let two_128 = U256::from_limbs([0, 0, 1, 0]);
+
+        // add / sub
+        assert_eq!(U256::MAX.checked_add(U256::ONE), None);
+        assert_eq!(U256::ZERO.checked_sub(U256::ONE), None);
+        assert_eq!(
+            U256::from(u64::MAX).checked_add(U256::ONE),
+            Some(U256::from_limbs([0, 1, 0, 0]))
+        );
+        assert_eq!(
+            U256::from_limbs([0, 1, 0, 0]).checked_sub(U256::ONE),
+            Some(U256::from(u64::MAX))
+        );
+
+        // mul
+        assert_eq!(two_128.checked_mul(two_128), None);
+        assert_eq!(U256::MAX.checked_mul(U256::from(2u64)), None);
+        assert_eq!(
+            U256::from(u128::MAX).checked_mul(U256::from(u128::MAX)),
+            Some(U256::from_limbs([1, 0, u64::MAX - 1, u64::MAX]))
+        );
+        assert_eq!(U256::MAX.checked_mul(U256::ONE), Some(U256::MAX));
+
+        // ordering ( high limb first )
+        assert!(two_128 > U256::from(u128::MAX));
+    }
+
+    #[test]
+    fn units_round_trip() {
+        // convert - fractional & whole amounts
+        assert_eq!(btc_to_sat("1.5"), Ok(U256::from(150_000_000u64)));
+        assert_eq!(btc_to_sat("0.00000001"), Ok(U256::ONE));
+        assert_eq!(sat_to_btc(U256::from(150_000_000u64)), "1.5");
+        assert_eq!(sat_to_btc(U256::ONE), "0.00000001");
+        assert_eq!(sat_to_btc(U256::ZERO), "0");
+        assert_eq!(trx_to_sun("12"), Ok(U256::from(12_000_000u64)));
+        assert_eq!(sun_to_trx(U256::from(12_000_000u64)), "12");
+
+        // round trip - beyond u64 ( 1e9 ether )
+        let wei = eth_to_wei("1000000000.000000000000000001").unwrap();
+        assert_eq!(wei.to_dec_str(), "1000000000000000000000000001");
+        assert_eq!(wei_to_eth(wei), "1000000000.000000000000000001");
+
+        // check - rejected amounts
+        assert_eq!(
+            btc_to_sat("0.000000001"),
+            Err(UnitsError::AmountTooPrecise {
+                decimals: 8,
+                got: 9
+            })
+        );
+        for amount in ["", ".5", "1.", "1.2.3"] {
+            assert!(btc_to_sat(amount).is_err(), "{:?}", amount);
+        }
+        assert_eq!(
+            btc_to_sat("-1"),
+            Err(UnitsError::AmountParseFail(TypesError::DecStrInvalidChar(
+                "-".to_string()
+            )))
+        );
+    }
+
+    #[test]
+    fn u256_dec_str() {
+        let max = "115792089237316195423570985008687907853269984665640564039457584007913129639935";
+
+        // round trip
+        for dec in [
+            "0",
+            "1",
+            "10000000000000000000",
+            "18446744073709551616",
+            "340282366920938463463374607431768211456",
+            max,
+        ] {
+            let value = U256::from_dec_str(dec).unwrap();
+            assert_eq!(value.to_dec_str(), dec);
+        }
+        assert_eq!(U256::from_dec_str(max), Ok(U256::MAX));
+        assert_eq!(U256::from(u128::MAX).to_string(), u128::MAX.to_string());
+
+        // invalid
+        assert_eq!(U256::from_dec_str(""), Err(TypesError::DecStrEmpty));
+        assert_eq!(
+            U256::from_dec_str("12a"),
+            Err(TypesError::DecStrInvalidChar("a".to_string()))
+        );
+        let overflow =
+            "115792089237316195423570985008687907853269984665640564039457584007913129639936";
+        assert_eq!(
+            U256::from_dec_str(overflow),
+            Err(TypesError::DecStrOverflow(overflow.to_string()))
+        );
+
+        // borsh - 32 bytes, little endian
+        let encoded = borsh::to_vec(&U256::from(258u64)).unwrap();
+        assert_eq!(encoded.len(), 32);
+        assert_eq!(encoded[0..2], [2, 1]);
+        assert_eq!(U256::try_from_slice(&encoded).unwrap(), U256::from(258u64));
+    }
+
+    #[test]
+    fn vm_run_result_rpc_json() {
+        // round trip - success
+        let vm_ret =
+            VmRunResult::new(None, ProgramCode::Ok, vec![0x00, 0xAB, 0xFF], 1200).with_refund(2000);
+        let json = vm_ret.to_rpc_json();
+        assert_eq!(
+            json,
+            r#"{"error":null,"program_code":"Ok","program_data":"0x00abff","gas_used":1200,"gas_refund":800,"mem_peak_bytes":0}"#
+        );
+        let decoded = VmRunResult::from_rpc_json(&json).unwrap();
+        assert_eq!(
+            borsh::to_vec(&decoded).unwrap(),
+            borsh::to_vec(&vm_ret).unwrap()
+        );
+
+        // round trip - error
+        let vm_ret = VmRunResult::new(
+            Some(EmVmError::RetProgramMemReadFail(
+                EmMemError::MemoryReadDataFail("oob".to_string()),
+            )),
+            ProgramCode::UndefinedErrPtr,
+            vec![],
+            7,
+        );
+        let json = vm_ret.to_rpc_json();
+        assert!(
+            json.contains(
+                r#""error":{"kind":"RetProgramMemReadFail","detail":{"MemoryReadDataFail":"oob"}}"#
+            ),
+            "{}",
+            json
+        );
+        let decoded = VmRunResult::from_rpc_json(&json).unwrap();
+        assert_eq!(decoded.error, vm_ret.error);
+        assert_eq!(
+            borsh::to_vec(&decoded).unwrap(),
+            borsh::to_vec(&vm_ret).unwrap()
+        );
+
+        // round trip - unit error, same { kind, detail } shape
+        let vm_ret = VmRunResult::new(
+            Some(EmVmError::NewOpcodeBinaryEmpty),
+            ProgramCode::VmError,
+            vec![],
+            0,
+        );
+        let unit_json = vm_ret.to_rpc_json();
+        assert!(
+            unit_json.contains(r#""error":{"kind":"NewOpcodeBinaryEmpty","detail":null}"#),
+            "{}",
+            unit_json
+        );
+        let decoded = VmRunResult::from_rpc_json(&unit_json).unwrap();
+        assert_eq!(decoded.error, Some(EmVmError::NewOpcodeBinaryEmpty));
+        assert_eq!(
+            borsh::to_vec(&decoded).unwrap(),
+            borsh::to_vec(&vm_ret).unwrap()
+        );
+
+        // decode - invalid
+        let ret = VmRunResult::from_rpc_json(&json.replace("0x", ""));
+        assert!(matches!(ret, Err(EmVmError::RpcJsonDecodeFail(_))));
+        let ret = VmRunResult::from_rpc_json(&unit_json.replace("NewOpcodeBinaryEmpty", "Missing"));
+        assert!(matches!(ret, Err(EmVmError::RpcJsonDecodeFail(_))));
+    }
+
+    #[test]
+    fn custom_sections() {
+        // init - module + custom sections ( id 0, size, name len, name, data )
+        let mut opcode = wat2wasm(WAT_SPIN.as_bytes()).unwrap().to_vec();
+        for (name, data) in [
+            ("name_meta", &b"v1.0.0"[..]),
+            ("abi", &b"{\"fn\":\"spin\"}"[..]),
+        ] {
+            opcode.push(0);
+            opcode.push((1 + name.len() + data.len()) as u8);
+            opcode.push(name.len() as u8);
+            opcode.extend_from_slice(name.as_bytes());
+            opcode.extend_from_slice(data);
+        }
+
+        // read - without instantiating
+        assert_eq!(
+            VmModule::list_custom_sections(&opcode),
+            vec!["abi".to_string(), "name_meta".to_string()]
+        );
+        assert_eq!(
+            VmModule::read_custom_section(&opcode, "abi"),
+            Some(b"{\"fn\":\"spin\"}".to_vec())
+        );
+        assert_eq!(VmModule::read_custom_section(&opcode, "missing"), None);
+
+        // check - still a loadable module
+        assert!(VMLauncher::new(&opcode, false, false, None).is_ok());
+    }
+
+    #[test]
+    fn start_function_policy() {
+        let opcode = WAT_START.as_bytes();
+
+        // init - metered rejects by default
+        let launcher = VMLauncher::new(opcode, false, true, None);
+        assert_eq!(
+            launcher.err(),
+            Some(EmVmError::NewModuleInitBinaryFail(
+                ModuleError::StartFunctionNotAllowed
+            ))
+        );
+
+        // init - unmetered allows by default, start ran at instantiation
+        let launcher = VMLauncher::new(opcode, false, false, None);
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        let globals = launcher.unwrap().snapshot_globals();
+        assert_eq!(globals, vec![("counter".to_string(), Value::I32(1))]);
+
+        // init - metered, explicitly allowed ( start traps on the 0 gas budget )
+        let config = VmConfig {
+            allow_start_function: Some(true),
+            ..VmConfig::default()
+        };
+        let launcher = VMLauncher::new_with_config(opcode, false, true, None, config);
+        assert!(
+            matches!(launcher.err(), Some(EmVmError::NewInstanceStartTrap(_))),
+            "start fn should trap without gas"
+        );
+
+        // init - serialized module with a start fn, metered rejects it after deserialization
+        let config = VmConfig {
+            allow_start_function: Some(true),
+            ..VmConfig::default()
+        };
+        let compiled = CompiledModule::new(opcode, false, true, None, config).unwrap();
+        assert!(compiled.vm_module.has_start());
+        let module_opcode = compiled.vm_module.export_module_opcode().unwrap();
+        let launcher = VMLauncher::new(&module_opcode, true, true, None);
+        assert_eq!(
+            launcher.err(),
+            Some(EmVmError::NewModuleInitEncodedFail(
+                ModuleError::StartFunctionNotAllowed
+            ))
+        );
+
+        // check - binary & modules without start
+        assert_eq!(
+            VmModule::has_start_function(&wat2wasm(opcode).unwrap()),
+            Ok(true)
+        );
+        assert_eq!(VmModule::has_start_function(WAT_SPIN.as_bytes()), Ok(false));
+    }
+
+    #[test]
+    fn module_policy_limits() {
+        let opcode = WAT_LIMITS.as_bytes();
+        assert_eq!(
+            VmModule::check_policy(opcode, &ModulePolicy::default()),
+            Ok(())
+        );
+
+        // check - each limit at 1
+        let cases = [
+            (
+                ModuleLimit::Imports,
+                ModulePolicy {
+                    max_imports: 1,
+                    ..ModulePolicy::default()
+                },
+            ),
+            (
+                ModuleLimit::Exports,
+                ModulePolicy {
+                    max_exports: 1,
+                    ..ModulePolicy::default()
+                },
+            ),
+            (
+                ModuleLimit::Functions,
+                ModulePolicy {
+                    max_functions: 1,
+                    ..ModulePolicy::default()
+                },
+            ),
+            (
+                ModuleLimit::MemoryPages,
+                ModulePolicy {
+                    max_memory_pages_declared: 1,
+                    ..ModulePolicy::default()
+                },
+            ),
+            (
+                ModuleLimit::Globals,
+                ModulePolicy {
+                    max_globals: 1,
+                    ..ModulePolicy::default()
+                },
+            ),
+        ];
+        for (limit, policy) in cases {
+            assert_eq!(
+                VmModule::check_policy(opcode, &policy),
+                Err(ModuleError::LimitExceeded {
+                    limit,
+                    max: 1,
+                    got: 2
+                })
+            );
+        }
+
+        // init - enforced by the launcher
+        let config = VmConfig {
+            module_policy: ModulePolicy {
+                max_exports: 1,
+                ..ModulePolicy::default()
+            },
+            ..VmConfig::default()
+        };
+        let launcher =
+            VMLauncher::new_with_config(WAT_COUNTER.as_bytes(), false, false, None, config);
+        assert_eq!(
+            launcher.err(),
+            Some(EmVmError::NewModuleInitBinaryFail(
+                ModuleError::LimitExceeded {
+                    limit: ModuleLimit::Exports,
+                    max: 1,
+                    got: 4,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn vm_run_result_receipt() {
+        // round trip - typical success
+        let vm_ret = VmRunResult::new(None, ProgramCode::Ok, vec![0, 100, 250, 0, 1, 2], 84210);
+        let receipt = vm_ret.to_receipt_bytes();
+        let borsh_bytes = borsh::to_vec(&vm_ret).unwrap();
+        assert_eq!(receipt.len(), 12);
+        assert!(
+            receipt.len() < borsh_bytes.len() / 2,
+            "{} vs {}",
+            receipt.len(),
+            borsh_bytes.len()
+        );
+        let decoded = VmRunResult::from_receipt_bytes(&receipt).unwrap();
+        assert_eq!(borsh::to_vec(&decoded).unwrap(), borsh_bytes);
+
+        // round trip - error path
+        let vm_ret = VmRunResult::new(
+            Some(EmVmError::FunctionCallFail("trap".to_string())),
+            ProgramCode::UnknownError,
+            vec![],
+            u64::MAX,
+        );
+        let decoded = VmRunResult::from_receipt_bytes(&vm_ret.to_receipt_bytes()).unwrap();
+        assert_eq!(
+            borsh::to_vec(&decoded).unwrap(),
+            borsh::to_vec(&vm_ret).unwrap()
+        );
+
+        // decode - truncated / trailing bytes
+        assert!(VmRunResult::from_receipt_bytes(&receipt[..receipt.len() - 1]).is_err());
+        let mut trailing = receipt.clone();
+        trailing.push(0);
+        assert!(VmRunResult::from_receipt_bytes(&trailing).is_err());
+    }
+
+    #[test]
+    fn set_gas_remaining_gas() {
+        let mut launcher = VMLauncher::new(WAT_WORK.as_bytes(), false, true, None).unwrap();
+
+        // set / read back
+        launcher.set_gas(1234).unwrap();
+        assert_eq!(launcher.remaining_gas(), Ok(1234));
+
+        // run - consumption matches the remaining points
+        let vm_ret = launcher.run_with_fuel(100000, "work", &[Value::I32(100)]);
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::Ok),
+            "{:?}",
+            vm_ret
+        );
+        assert_eq!(vm_ret.gas_used, 100000 - launcher.remaining_gas().unwrap());
+
+        // check - unmetered
+        assert!(launcher.is_metered());
+        let mut launcher = VMLauncher::new(WAT_WORK.as_bytes(), false, false, None).unwrap();
+        assert!(!launcher.is_metered());
+        assert_eq!(launcher.set_gas(1), Err(EmVmError::GasMeteringDisabled));
+        assert_eq!(
+            launcher.remaining_gas(),
+            Err(EmVmError::GasMeteringDisabled)
+        );
+    }
+
+    #[test]
+    fn unmetered_trap_not_out_of_gas() {
+        let mut launcher = VMLauncher::new(WAT_TRAP.as_bytes(), false, false, None).unwrap();
+
+        // run - gas args are ignored, the trap is not reported as OutOfGas
+        let vm_ret = launcher.run(1, 1000, "trap");
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::UnknownError),
+            "{:?}",
+            vm_ret
+        );
+        assert!(matches!(vm_ret.error, Some(EmVmError::FunctionCallFail(_))));
+        assert_eq!(vm_ret.gas_used, 0);
+    }
+
+    #[test]
+    fn instance_error_import_missing() {
+        // init - fn outside "env" & global import, neither supplied by the host
+        for (opcode, name) in [
+            (
+                r#"(module (import "host" "f" (func)) (memory (export "memory") 1))"#,
+                "host.f",
+            ),
+            (
+                r#"(module (import "env" "g" (global i32)) (memory (export "memory") 1))"#,
+                "env.g",
+            ),
+        ] {
+            let launcher = VMLauncher::new(opcode.as_bytes(), false, false, None);
+            assert_eq!(
+                launcher.err(),
+                Some(EmVmError::NewInstanceImportMissing(name.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn compiled_module_shared_across_threads() {
+        let compiled = Arc::new(
+            CompiledModule::new(WAT_WORK.as_bytes(), false, true, None, VmConfig::default())
+                .unwrap(),
+        );
+        assert!(compiled.is_metered());
+
+        // run - one launcher per thread, one compilation
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let compiled = compiled.clone();
+                std::thread::spawn(move || {
+                    let mut launcher = VMLauncher::from_compiled(compiled).unwrap();
+                    launcher.run_with_fuel(100000, "work", &[Value::I32(100)])
+                })
+            })
+            .collect();
+        let vm_rets: Vec<VmRunResult> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        // check - same result as a launcher compiled on its own
+        let vm_ret = VMLauncher::new(WAT_WORK.as_bytes(), false, true, None)
+            .unwrap()
+            .run_with_fuel(100000, "work", &[Value::I32(100)]);
+        for vm_ret_thread in vm_rets {
+            assert!(
+                matches!(vm_ret_thread.program_code, ProgramCode::Ok),
+                "{:?}",
+                vm_ret_thread
+            );
+            assert_eq!(vm_ret_thread.gas_used, vm_ret.gas_used);
+        }
+    }
+
+    #[test]
+    fn run_borsh_echo() {
+        #[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize)]
+        struct Transfer {
+            to: String,
+            amount: u64,
+        }
+
+        let mut launcher = VMLauncher::new(WAT_BORSH_ECHO.as_bytes(), false, false, None).unwrap();
+        let transfer = Transfer {
+            to: "alice".to_string(),
+            amount: 42,
+        };
+
+        // run - round trip
+        let echoed: Result<Transfer, EmVmError> = launcher.run_borsh(0, 0, "echo", &transfer);
+        assert_eq!(echoed, Ok(transfer));
+
+        // run - guest borsh code ( surfaced by ret_program despite trailing data )
+        let vm_ret = launcher.run_with_input(0, 0, "reject", &[]);
+        assert_eq!(vm_ret.program_code, ProgramCode::BorshDecodeInvalidArg);
+        let rejected: Result<Transfer, EmVmError> = launcher.run_borsh(0, 0, "reject", &0u8);
+        assert_eq!(rejected, Err(EmVmError::ProgramBorshDecodeFail));
+
+        // run - output not matching the expected type
+        let mismatched: Result<Transfer, EmVmError> = launcher.run_borsh(0, 0, "echo", &1u8);
+        assert!(
+            matches!(mismatched, Err(EmVmError::BorshOutputDecodeFail(_))),
+            "{:?}",
+            mismatched
+        );
+    }
+
+    #[test]
+    fn shutdown_returns_external() {
+        // init - external updated by a host fn
+        let host_charge: ImportedFn<u32> = Box::new(|mut env, args| {
+            *env.data_mut().1.as_mut().unwrap() += args[0].unwrap_i32() as u32;
+            Ok(vec![])
+        });
+        let mut imported_fn = HashMap::new();
+        imported_fn.insert(
+            "host_charge".to_string(),
+            (host_charge, FunctionType::new(vec![Type::I32], vec![])),
+        );
+        let mut launcher = VMLauncher::new_with_external(
+            WAT_HOST_CHARGE.as_bytes(),
+            false,
+            false,
+            7u32,
+            imported_fn,
+            None,
+        )
+        .unwrap();
+
+        // run - external += 5000
+        let vm_ret = launcher.run(0, 0, "charge_once");
+        assert!(vm_ret.error.is_none(), "{:?}", vm_ret);
+        assert_eq!(launcher.shutdown(), 5007);
+
+        // check - external taken by a host fn, construction value returned
+        let host_charge: ImportedFn<u32> = Box::new(|mut env, _| {
+            env.data_mut().1.take();
+            Ok(vec![])
+        });
+        let mut imported_fn = HashMap::new();
+        imported_fn.insert(
+            "host_charge".to_string(),
+            (host_charge, FunctionType::new(vec![Type::I32], vec![])),
+        );
+        let mut launcher = VMLauncher::new_with_external(
+            WAT_HOST_CHARGE.as_bytes(),
+            false,
+            false,
+            7u32,
+            imported_fn,
+            None,
+        )
+        .unwrap();
+        let vm_ret = launcher.run(0, 0, "charge_once");
+        assert!(vm_ret.error.is_none(), "{:?}", vm_ret);
+        assert_eq!(launcher.shutdown(), 7);
+    }
+
+    #[test]
+    fn function_index_by_export() {
+        // check - main.wasm
+        let launcher = VMLauncher::new(&load_file(FILE_PATH_WASM), false, true, None).unwrap();
+        assert!(launcher.function_index("example").is_some());
+        assert_eq!(launcher.function_index("bogus"), None);
+
+        // check - imported fn takes index 0, memory is not a fn
+        let host_fn: ImportedFn<u32> = Box::new(|_env, _args| Ok(vec![]));
+        let mut imported_fn = HashMap::new();
+        imported_fn.insert(
+            "a".to_string(),
+            (host_fn, FunctionType::new(vec![], vec![])),
+        );
+        let opcode = r#"(module (import "env" "a" (func)) (memory (export "memory") 1) (func (export "f")))"#;
+        let launcher =
+            VMLauncher::new_with_external(opcode.as_bytes(), false, false, 0, imported_fn, None)
+                .unwrap();
+        assert_eq!(launcher.function_index("f"), Some(1));
+        assert_eq!(launcher.function_index("memory"), None);
+    }
+
+    #[test]
+    fn run_sequence_shares_state_and_gas() {
+        let gas_limit = 100000;
+        let calls = vec![
+            ("store".to_string(), vec![Value::I32(7)]),
+            ("load".to_string(), vec![]),
+        ];
+
+        // run - second call reads what the first wrote
+        let mut launcher = VMLauncher::new(WAT_STORE_LOAD.as_bytes(), false, true, None).unwrap();
+        let vm_rets = launcher.run_sequence(1, gas_limit, &calls);
+        assert_eq!(vm_rets.len(), 2);
+        assert_eq!(vm_rets[0].program_code, ProgramCode::NoReturn);
+        assert_eq!(vm_rets[1].program_code, ProgramCode::Ok);
+        assert_eq!(vm_rets[1].program_data, 7u32.to_le_bytes().to_vec());
+
+        // check - gas is deducted across calls
+        let gas_used: u64 = vm_rets.iter().map(|vm_ret| vm_ret.gas_used).sum();
+        assert_eq!(vm_rets[1].gas_refund, gas_limit - gas_used);
+
+        // run - stops at the first failed call
+        let calls = vec![
+            ("missing".to_string(), vec![]),
+            ("load".to_string(), vec![]),
+        ];
+        let vm_rets = launcher.run_sequence(1, gas_limit, &calls);
+        assert_eq!(vm_rets.len(), 1);
+        assert_eq!(vm_rets[0].program_code, ProgramCode::FnInvalidEntryPoint);
+    }
+
+    #[test]
+    fn host_fn_panic_is_trapped() {
+        let host_charge: ImportedFn<u32> = Box::new(|_env, _args| panic!("host state corrupted"));
+        let mut imported_fn = HashMap::new();
+        imported_fn.insert(
+            "host_charge".to_string(),
+            (host_charge, FunctionType::new(vec![Type::I32], vec![])),
+        );
+        let mut launcher = VMLauncher::new_with_external(
+            WAT_HOST_CHARGE.as_bytes(),
+            false,
+            true,
+            0,
+            imported_fn,
+            None,
+        )
+        .unwrap();
+
+        // run - panic surfaces as a result, not an unwind
+        let vm_ret = launcher.run(1, 100000, "charge_once");
+        assert_eq!(vm_ret.program_code, ProgramCode::HostPanic);
+        assert_eq!(
+            vm_ret.error,
+            Some(EmVmError::FunctionCallHostPanic(
+                "host state corrupted".to_string()
+            ))
+        );
+        assert!(vm_ret.gas_used > 0);
+
+        // check - code round trip
+        let code = ProgramCode::HostPanic.to_i32();
+        assert_eq!(ProgramCode::from_i32(code), ProgramCode::HostPanic);
+    }
+
+    #[test]
+    fn validate_without_instance() {
+        // check - main.wasm
+        let opcode = load_file(FILE_PATH_WASM);
+        assert_eq!(VMLauncher::validate(&opcode, false, true), Ok(()));
+
+        // check - missing host imports are an instantiation concern, not a compile one
+        assert_eq!(
+            VMLauncher::validate(WAT_IMPORT_MANY.as_bytes(), false, true),
+            Ok(())
+        );
+
+        // check - garbage
+        let ret = VMLauncher::validate(&[0xDE, 0xAD, 0xBE, 0xEF], false, true);
+        assert!(
+            matches!(ret, Err(EmVmError::NewModuleInitBinaryFail(_))),
+            "{:?}",
+            ret
+        );
+    }
+
+    #[test]
+    fn module_operators_distinct() {
+        let operators = VmModule::operators(&load_file(FILE_PATH_WASM)).unwrap();
+        assert!(!operators.is_empty());
+
+        // check - sorted & distinct, covers ops the test schedule prices
+        let mut sorted = operators.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(operators, sorted);
+        for name in ["I32Const", "I32Add", "LocalGet", "End"] {
+            assert!(
+                operators.contains(&name.to_string()),
+                "{} in {:?}",
+                name,
+                operators
+            );
+        }
+
+        // check - wat
+        assert_eq!(
+            VmModule::operators(WAT_TRAP.as_bytes()),
+            Ok(vec!["End".to_string(), "Unreachable".to_string()])
+        );
+    }
+
+    #[test]
+    fn read_globals_after_run() {
+        let opcode = r#"
+            (module
+                (memory (export "memory") 1)
+                (global (export "version") i32 (i32.const 3))
+                (global $state (export "state") (mut i64) (i64.const 0))
+                (func (export "advance")
+                    i64.const 2
+                    global.set $state))
+        "#;
+        let mut launcher = VMLauncher::new(opcode.as_bytes(), false, true, None).unwrap();
+
+        // run - state changes, metering globals stay hidden
+        let vm_ret = launcher.run(1, 100000, "advance");
+        assert!(vm_ret.error.is_none(), "{:?}", vm_ret);
+        let mut globals = launcher.read_globals();
+        globals.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            globals,
+            vec![
+                ("state".to_string(), Value::I64(2)),
+                ("version".to_string(), Value::I32(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn launcher_deadline() {
+        let opcode = load_file(FILE_PATH_WASM);
+
+        // run - within the deadline
+        let deadline = Instant::now() + std::time::Duration::from_secs(60);
+        let mut launcher =
+            VMLauncher::new_with_deadline(&opcode, false, true, None, deadline).unwrap();
+        let vm_ret = launcher.run(1, 10000000, "example");
+        assert!(vm_ret.error.is_none(), "{:?}", vm_ret);
+
+        // run - deadline passed
+        launcher.set_deadline(Some(Instant::now()));
+        let vm_ret = launcher.run(1, 10000000, "example");
+        assert_eq!(vm_ret.program_code, ProgramCode::Timeout);
+        assert_eq!(
+            vm_ret.error,
+            Some(EmVmError::DeadlineExceeded(DeadlinePhase::Run))
+        );
+        assert_eq!(vm_ret.gas_refund, 10000000);
+    }
+
+    #[test]
+    fn launcher_deadline_slow_compile() {
+        // init - 500 fns, far more compile time than the deadline leaves
+        let mut opcode = String::from("(module (memory (export \"memory\") 1)");
+        for index in 0..500 {
+            opcode.push_str(&format!(
+                "(func $f{} (param i32) (result i32) {})",
+                index,
+                "local.get 0 i32.const 7 i32.mul i32.const 3 i32.add local.set 0 ".repeat(10)
+                    + "local.get 0"
+            ));
+        }
+        opcode.push(')');
+
+        // init - deadline trips once compilation returns ( compilation itself is not aborted )
+        let deadline = Instant::now() + std::time::Duration::from_millis(5);
+        let launcher =
+            VMLauncher::new_with_deadline(opcode.as_bytes(), false, true, None, deadline);
+        assert_eq!(
+            launcher.err(),
+            Some(EmVmError::DeadlineExceeded(DeadlinePhase::Compile))
+        );
+    }
+
+    #[test]
+    fn launcher_deadline_interrupts_run() {
+        // init - host fn, unmetered ( nothing but the deadline bounds `spin` )
+        let host_add: ImportedFn<()> =
+            Box::new(|_, args| Ok(vec![Value::I32(args[0].unwrap_i32() + 100)]));
+        let mut imported_fn = HashMap::new();
+        imported_fn.insert(
+            "host_add".to_string(),
+            (
+                host_add,
+                FunctionType::new(vec![Type::I32], vec![Type::I32]),
+            ),
+        );
+        let deadline = Instant::now() + std::time::Duration::from_secs(60);
+        let mut launcher = VMLauncher::new_with_external_deadline(
+            WAT_INTERRUPT.as_bytes(),
+            false,
+            false,
+            (),
+            imported_fn,
+            None,
+            VmConfig::default(),
+            deadline,
+        )
+        .unwrap();
+        assert_eq!(launcher.list_exports(), vec!["calls", "memory", "spin"]);
+
+        // run - calls through the shifted fn indexes
+        let vm_ret = launcher.run_with_fuel(0, "calls", &[]);
+        assert_eq!(vm_ret.program_code, ProgramCode::Ok, "{:?}", vm_ret);
+        assert_eq!(vm_ret.program_data, 212u32.to_le_bytes().to_vec());
+
+        // run - endless loop interrupted at the deadline
+        launcher.set_deadline(Some(Instant::now() + std::time::Duration::from_millis(100)));
+        let vm_ret = launcher.run_with_fuel(0, "spin", &[]);
+        assert_eq!(vm_ret.program_code, ProgramCode::Timeout, "{:?}", vm_ret);
+        assert_eq!(
+            vm_ret.error,
+            Some(EmVmError::DeadlineExceeded(DeadlinePhase::Run))
+        );
+
+        // run - next call within a new deadline
+        launcher.set_deadline(Some(Instant::now() + std::time::Duration::from_secs(60)));
+        let vm_ret = launcher.run_with_fuel(0, "calls", &[]);
+        assert_eq!(vm_ret.program_code, ProgramCode::Ok, "{:?}", vm_ret);
+        assert_eq!(vm_ret.program_data, 212u32.to_le_bytes().to_vec());
+    }
+
+    #[test]
+    fn list_exports_sorted() {
+        let opcode = r#"
+            (module
+                (memory (export "memory") 1)
+                (global (export "zeta") i32 (i32.const 0))
+                (func (export "beta"))
+                (func (export "alpha")))
+        "#;
+        let launcher = VMLauncher::new(opcode.as_bytes(), false, true, None).unwrap();
+
+        // check - sorted, metering globals hidden
+        assert_eq!(
+            launcher.list_exports(),
+            vec!["alpha", "beta", "memory", "zeta"]
+        );
+    }
+
+    #[test]
+    fn module_policy_forbid_float_ops() {
+        let config = VmConfig {
+            module_policy: ModulePolicy {
+                forbid_float_ops: true,
+                ..ModulePolicy::default()
+            },
+            ..VmConfig::default()
+        };
+        let opcode = r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "add") (param f64 f64) (result f64)
+                    local.get 0
+                    local.get 1
+                    f64.add))
+        "#;
+
+        // init - f64 signature rejected ( type section comes first )
+        let launcher =
+            VMLauncher::new_with_config(opcode.as_bytes(), false, true, None, config.clone());
+        assert_eq!(
+            launcher.err(),
+            Some(EmVmError::NewModuleInitBinaryFail(
+                ModuleError::FloatOpForbidden("type F64".to_string())
+            ))
+        );
+
+        // init - float types outside of code, float ops in integer signatures
+        let cases = [
+            (
+                r#"(module (import "env" "rate" (global f32)) (memory (export "memory") 1))"#,
+                "import env.rate F32",
+            ),
+            (
+                r#"(module (memory (export "memory") 1) (global f64 (f64.const 0)))"#,
+                "global F64",
+            ),
+            (
+                r#"(module (memory (export "memory") 1) (func (local f32)))"#,
+                "local F32",
+            ),
+            (
+                r#"(module (memory (export "memory") 1)
+                    (func (param i32) (result i32) local.get 0 f32.convert_i32_s i32.trunc_f32_s))"#,
+                "F32ConvertI32S",
+            ),
+        ];
+        for (opcode, forbidden) in cases {
+            let ret = VmModule::check_policy(opcode.as_bytes(), &config.module_policy);
+            assert_eq!(
+                ret,
+                Err(ModuleError::FloatOpForbidden(forbidden.to_string())),
+                "{}",
+                opcode
+            );
+        }
+
+        // init - integer only, and floats allowed by default
+        let launcher = VMLauncher::new_with_config(WAT_WORK.as_bytes(), false, true, None, config);
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        assert!(VMLauncher::new(opcode.as_bytes(), false, true, None).is_ok());
+    }
+
+    #[test]
+    fn run_result_encoded_len() {
+        let vm_rets = [
+            VmRunResult::new(None, ProgramCode::Ok, vec![1, 2, 3], 42),
+            VmRunResult::new(None, ProgramCode::NoReturn, vec![], 0),
+            VmRunResult::new(
+                Some(EmVmError::FunctionCallFail("trap".to_string())),
+                ProgramCode::UnknownError,
+                vec![],
+                7,
+            ),
+            VmRunResult::new(
+                Some(EmVmError::ImportMissing(vec![
+                    "a".to_string(),
+                    "bc".to_string(),
+                ])),
+                ProgramCode::VmError,
+                vec![0; 300],
+                u64::MAX,
+            )
+            .with_refund(u64::MAX),
+        ];
+        for vm_ret in vm_rets {
+            assert_eq!(vm_ret.encoded_len(), borsh::to_vec(&vm_ret).unwrap().len());
+        }
+    }
+
+    #[test]
+    fn run_speculative_rolls_back() {
+        let mut launcher = VMLauncher::new(WAT_COUNTER.as_bytes(), false, true, None).unwrap();
+        let memory = launcher.snapshot_memory().unwrap();
+
+        // run - counter & memory[2048] rolled back
+        let vm_ret = launcher.run_speculative(1, 100000, "bump", &[]);
+        assert_eq!(vm_ret.program_code, ProgramCode::NoReturn);
+        assert!(vm_ret.gas_used > 0);
+        assert_eq!(
+            launcher.snapshot_globals(),
+            vec![("counter".to_string(), Value::I32(0))]
+        );
+        assert_eq!(launcher.snapshot_memory().unwrap(), memory);
+
+        // run - committed as usual
+        launcher.run(1, 100000, "bump");
+        assert_eq!(
+            launcher.snapshot_globals(),
+            vec![("counter".to_string(), Value::I32(1))]
+        );
+
+        // run - grown pages are zeroed
+        let mut launcher = VMLauncher::new(WAT_GROW.as_bytes(), false, false, None).unwrap();
+        let memory = launcher.snapshot_memory().unwrap();
+        let vm_ret = launcher.run_speculative(0, 0, "grow", &[Value::I32(1)]);
+        assert_eq!(vm_ret.program_code, ProgramCode::Ok);
+        let restored = launcher.snapshot_memory().unwrap();
+        assert_eq!(restored.len(), 2 * 65536);
+        assert_eq!(&restored[..65536], &memory[..]);
+        assert!(restored[65536..].iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn gas_charged_overflow() {
+        // check - product past u64::MAX clamps to the limit
+        assert_eq!(VMLauncher::<()>::gas_charged(u64::MAX, 0, 2, 1000), 1000);
+        assert_eq!(VMLauncher::<()>::gas_charged(100, 40, 3, 1000), 180);
+
+        // check - points left above the calc limit charge nothing
+        assert_eq!(VMLauncher::<()>::gas_charged(0, 500, 0, 0), 0);
+
+        // run - priority 0 on a metered launcher with points left ( used to underflow )
+        let mut launcher = VMLauncher::new(WAT_WORK.as_bytes(), false, true, None).unwrap();
+        launcher.set_gas(100000).unwrap();
+        let vm_ret = launcher.call(0, 0, "work", &[Value::I32(1)]);
+        assert_eq!(vm_ret.program_code, ProgramCode::Ok, "{:?}", vm_ret);
+        assert_eq!(vm_ret.gas_used, 0);
+    }
+
+    #[test]
+    fn host_fn_gas_handle_threshold() {
+        let opcode = WAT_HOST_CHARGE.as_bytes();
+        let new_launcher = |gas_metering_used: bool| {
+            // host fn refusing to start below 10000 points, then pricing itself
+            let host_charge: ImportedFn<u32> = Box::new(|mut env, args| {
+                let mut gas = VmData::gas_handle(&mut env);
+                if gas.remaining().is_some_and(|remaining| remaining < 10000) {
+                    return Err(RuntimeError::new("gas below host threshold"));
+                }
+                gas.charge(args[0].unwrap_i32() as u64)?;
+                Ok(vec![])
+            });
+            let mut imported_fn = HashMap::new();
+            imported_fn.insert(
+                "host_charge".to_string(),
+                (host_charge, FunctionType::new(vec![Type::I32], vec![])),
+            );
+            VMLauncher::new_with_external(opcode, false, gas_metering_used, 0, imported_fn, None)
+                .unwrap()
+        };
+
+        // run - enough gas, host charge included
+        let vm_ret = new_launcher(true).run_with_fuel(100000, "charge_once", &[]);
+        assert_eq!(vm_ret.program_code, ProgramCode::Ok, "{:?}", vm_ret);
+        assert!(vm_ret.gas_used > 5000);
+
+        // run - below the threshold, refused before charging
+        let vm_ret = new_launcher(true).run_with_fuel(8000, "charge_once", &[]);
+        assert_eq!(vm_ret.program_code, ProgramCode::UnknownError);
+        assert!(vm_ret.gas_used < 5000);
+
+        // run - unmetered, no budget to check
+        let vm_ret = new_launcher(false).run(0, 0, "charge_once");
+        assert_eq!(vm_ret.program_code, ProgramCode::Ok, "{:?}", vm_ret);
+    }
+
+    #[test]
+    fn launcher_from_path() {
+        let fn_name = "example";
+
+        // run - &[u8] path
+        let opcode = load_file(FILE_PATH_WASM);
+        let mut launcher = VMLauncher::new(&opcode, false, true, None).unwrap();
+        let vm_ret_bytes = launcher.run(1, 10000000, fn_name);
+
+        // run - file path
+        let launcher = VMLauncher::from_path(FILE_PATH_WASM, false, true, None);
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        let vm_ret_path = launcher.unwrap().run(1, 10000000, fn_name);
+        assert_eq!(
+            borsh::to_vec(&vm_ret_path).unwrap(),
+            borsh::to_vec(&vm_ret_bytes).unwrap()
+        );
+
+        // init - missing file
+        let launcher = VMLauncher::from_path("missing.wasm", false, true, None);
+        assert!(matches!(
+            launcher.err(),
+            Some(EmVmError::NewOpcodeFileReadFail(_))
+        ));
+    }
+
+    #[test]
+    fn ret_program_no_return() {
+        let mut launcher = VMLauncher::new(WAT_COUNTER.as_bytes(), false, false, None).unwrap();
+
+        // run - void fn
+        let vm_ret = launcher.run(0, 0, "bump");
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::NoReturn),
+            "{:?}",
+            vm_ret
+        );
+        assert!(vm_ret.error.is_none());
+        assert!(vm_ret.program_data.is_empty());
+
+        // check - code round trip
+        let code = ProgramCode::NoReturn.to_i32();
+        assert!(matches!(ProgramCode::from_i32(code), ProgramCode::NoReturn));
+    }
+
+    #[test]
+    fn encoded_module_header() {
+        let opcode = load_file(FILE_PATH_WASM);
+
+        // init - raw wasm with opcode_module_used
+        let launcher = VMLauncher::new(&opcode, true, true, None);
+        assert_eq!(
+            launcher.err(),
+            Some(EmVmError::NewModuleInitEncodedFail(
+                ModuleError::InitByEncodedModuleFail(
+                    "expected serialized module, got raw wasm".to_string()
+                )
+            ))
+        );
+
+        // init - serialized module
+        let module_opcode = VMLauncher::new(&opcode, false, true, None)
+            .unwrap()
+            .get_module_opcode()
+            .unwrap();
+        assert!(module_opcode.starts_with(VmModule::SERIALIZED_MODULE_MAGIC));
+        assert!(VMLauncher::new(&module_opcode, true, true, None).is_ok());
+    }
+
+    #[test]
+    fn import_missing_sorted() {
+        let opcode = WAT_IMPORT_MANY.as_bytes();
+
+        // init - only "bravo" supplied, repeated to catch HashMap order leaking out
+        for _ in 0..8 {
+            let host_fn: ImportedFn<u32> = Box::new(|_env, _args| Ok(vec![]));
+            let mut imported_fn = HashMap::new();
+            imported_fn.insert(
+                "bravo".to_string(),
+                (host_fn, FunctionType::new(vec![], vec![])),
+            );
+            let launcher =
+                VMLauncher::new_with_external(opcode, false, false, 0, imported_fn, None);
+            assert_eq!(
+                launcher.err(),
+                Some(EmVmError::ImportMissing(vec![
+                    "alpha".to_string(),
+                    "charlie".to_string(),
+                    "delta".to_string(),
+                ]))
+            );
+        }
+    }
+
+    #[test]
+    fn mem_peak_bytes() {
+        let config = VmConfig {
+            mem_peak_tracked: true,
+            ..VmConfig::default()
+        };
+        let mut launcher =
+            VMLauncher::new_with_config(WAT_GROW.as_bytes(), false, true, None, config).unwrap();
+        let page = 65536;
+
+        // run - no growth
+        let vm_ret = launcher.run_with_fuel(100000, "grow", &[Value::I32(0)]);
+        assert_eq!(vm_ret.mem_peak_bytes, page);
+
+        // run - grow by 3 pages
+        let vm_ret = launcher.run_with_fuel(100000, "grow", &[Value::I32(3)]);
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::Ok),
+            "{:?}",
+            vm_ret
+        );
+        assert_eq!(vm_ret.mem_peak_bytes, 4 * page);
+
+        // run - after reset the peak starts over
+        launcher.reset().unwrap();
+        let vm_ret = launcher.run_with_fuel(100000, "grow", &[Value::I32(1)]);
+        assert_eq!(vm_ret.mem_peak_bytes, 2 * page);
+
+        // run - trap after a grow still reports the peak
+        launcher.reset().unwrap();
+        let vm_ret = launcher.run_with_fuel(100000, "grow_trap", &[Value::I32(2)]);
+        assert_eq!(
+            vm_ret.program_code,
+            ProgramCode::UnknownError,
+            "{:?}",
+            vm_ret
+        );
+        assert_eq!(vm_ret.mem_peak_bytes, 3 * page);
+        assert_eq!(
+            MemoryPeak::get_pages(&mut launcher.store, &launcher.instance),
+            3
+        );
+
+        // run - failed call before the guest runs keeps the peak
+        let vm_ret = launcher.run_with_fuel(100000, "missing", &[]);
+        assert_eq!(vm_ret.program_code, ProgramCode::FnInvalidEntryPoint);
+        assert_eq!(vm_ret.mem_peak_bytes, 3 * page);
+
+        // run - untracked ( default ), the size once the call returns
+        let mut launcher = VMLauncher::new(WAT_GROW.as_bytes(), false, true, None).unwrap();
+        let vm_ret = launcher.run_with_fuel(100000, "grow", &[Value::I32(2)]);
+        assert_eq!(vm_ret.mem_peak_bytes, 3 * page);
+        assert_eq!(
+            MemoryPeak::get_pages(&mut launcher.store, &launcher.instance),
+            0
+        );
+    }
+
+    #[test]
+    fn mem_codec_len64() {
+        let opcode = WAT_HOST_WRITE.as_bytes();
+        let config = VmConfig {
+            mem_codec: Some(Arc::new(Len64Codec)),
+            ..VmConfig::default()
+        };
+
+        // init - host fn writing an Ok result through the configured codec
+        let host_write: ImportedFn<u32> = Box::new(|mut env, _args| {
+            let (data, mut store) = env.data_and_store_mut();
+            let ptr = data
+                .0
+                .memory_write(&mut store, &[ProgramCode::Ok.to_i32() as u8, 7, 8, 9])
+                .map_err(|e| RuntimeError::new(format!("{:?}", e)))?;
+            Ok(vec![Value::I32(ptr as i32)])
+        });
+        let fn_type = FunctionType::new(vec![], vec![Type::I32]);
+        let mut imported_fn = HashMap::new();
+        imported_fn.insert("host_write".to_string(), (host_write, fn_type));
+        let launcher = VMLauncher::new_with_external_config(
+            opcode,
+            false,
+            false,
+            0,
+            imported_fn,
+            None,
+            config,
+        );
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        let mut launcher = launcher.unwrap();
+
+        // run - ret_program decodes with the same codec
+        let vm_ret = launcher.run(0, 0, "echo");
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::Ok),
+            "{:?}",
+            vm_ret
+        );
+        assert_eq!(vm_ret.program_data, vec![7, 8, 9]);
+
+        // check - 8byte length prefix in guest memory
+        let snapshot = launcher.snapshot_memory().unwrap();
+        assert_eq!(snapshot[1024..1032], 4u64.to_le_bytes());
+        assert_eq!(snapshot[1032..1036], [0, 7, 8, 9]);
+    }
+
+    #[test]
+    fn call_depth_limit() {
+        let opcode = WAT_REENTER.as_bytes();
+        let config = VmConfig {
+            max_call_depth: Some(16),
+            ..VmConfig::default()
+        };
+
+        // init - host fn re-entering the guest without a base case
+        let host_reenter: ImportedFn<u32> = Box::new(|mut env, args| {
+            let ret = VmData::call_export(&mut env, "reenter", args)?;
+            Ok(ret.to_vec())
+        });
+        let fn_type = FunctionType::new(vec![Type::I32], vec![Type::I32]);
+        let mut imported_fn = HashMap::new();
+        imported_fn.insert("host_reenter".to_string(), (host_reenter, fn_type));
+        let launcher =
+            VMLauncher::new_with_external_config(opcode, false, true, 0, imported_fn, None, config);
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        let mut launcher = launcher.unwrap();
+
+        // run - guard fires instead of overflowing the native stack
+        let vm_ret = launcher.run_with_fuel(10000000, "reenter", &[Value::I32(0)]);
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::CallDepthExceeded),
+            "{:?}",
+            vm_ret
+        );
+        assert_eq!(vm_ret.error, Some(EmVmError::FunctionCallDepthExceeded));
+    }
+
+    #[test]
+    fn stack_height_limit() {
+        let opcode = WAT_RECURSE.as_bytes();
+        let config = VmConfig {
+            middlewares: vec![Arc::new(StackHeightLimit::new(100))],
+            ..VmConfig::default()
+        };
+
+        // init - metering + stack height limit
+        let launcher = VMLauncher::new_with_config(opcode, false, true, None, config);
+        assert!(launcher.is_ok(), "{:?}", launcher.err());
+        let mut launcher = launcher.unwrap();
+
+        // run - within the limit
+        let vm_ret = launcher.run_with_fuel(10000000, "down", &[Value::I32(50)]);
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::Ok),
+            "{:?}",
+            vm_ret
+        );
+
+        // run - too deep
+        let vm_ret = launcher.run_with_fuel(10000000, "down", &[Value::I32(200)]);
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::StackHeightExceeded),
+            "{:?}",
+            vm_ret
+        );
+        assert!(vm_ret.gas_used > 0);
+
+        // run - depth is reset after the trap
+        let vm_ret = launcher.run_with_fuel(10000000, "down", &[Value::I32(99)]);
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::Ok),
+            "{:?}",
+            vm_ret
+        );
+    }
+
+    #[test]
+    fn stack_height_branch_exit() {
+        let opcode = WAT_BR_EXIT.as_bytes();
+        let config = VmConfig {
+            middlewares: vec![Arc::new(StackHeightLimit::new(100))],
+            ..VmConfig::default()
+        };
+        let mut launcher = VMLauncher::new_with_config(opcode, false, true, None, config).unwrap();
+
+        // run - branches out of the function give the frame back, far more calls than the limit
+        let vm_ret = launcher.run_with_fuel(10000000, "loop_br", &[Value::I32(500)]);
+        assert!(
+            matches!(vm_ret.program_code, ProgramCode::Ok),
+            "{:?}",
+            vm_ret
+        );
+    }
+
     fn get_opcode_type_module() -> Result<Vec<u8>, EmVmError> {
         let opcode = load_file(FILE_PATH_WASM);
         let is_module = false;
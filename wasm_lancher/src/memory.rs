@@ -1,12 +1,15 @@
 use borsh::{BorshDeserialize, BorshSerialize};
-use wasmer::{Instance, MemoryView, Store, StoreMut};
+use serde::{Deserialize, Serialize};
+use wasmer::{AsStoreMut, Function, Instance, MemoryView, Store, StoreMut, Type, Value};
 
-use memory::Memory;
+use memory::{MemCodec, Memory};
 
-#[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize, Clone)]
+#[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
 pub enum EmMemError {
     MemoryWriteFail(String),
     MemoryWriteLoadFail(String),
+    MemoryWriteOutOfBounds(String),
+    MemoryGrowFail(String),
 
     MemoryReadDataLenFail(String),
     MemoryReadDataFail(String),
@@ -15,8 +18,15 @@ pub enum EmMemError {
     MemoryAllocGetFnFail(String),
     MemoryAllocCallFnFail(String),
     MemoryAllocPtrEmpty,
+    MemoryAllocSizeOverflow(String),
+
+    MemoryFreeCallFnFail(String),
+    MemoryFreeSignatureInvalid(String),
 }
 
+pub const DEF_MEM_ALLOC_FN: &str = "mem_alloc";
+pub const DEF_MEM_FREE_FN: &str = "mem_free";
+
 pub struct VmMemory;
 
 impl VmMemory {
@@ -24,9 +34,20 @@ impl VmMemory {
         store: &mut Store,
         instance: &Instance,
         data: &[u8],
+    ) -> Result<u32, EmMemError> {
+        VmMemory::mem_write_store_with(store, instance, data, &Memory, DEF_MEM_ALLOC_FN)
+    }
+
+    pub fn mem_write_store_with(
+        store: &mut Store,
+        instance: &Instance,
+        data: &[u8],
+        codec: &dyn MemCodec,
+        alloc_fn: &str,
     ) -> Result<u32, EmMemError> {
         // alloc - memory
-        let ptr = VmMemory::mem_alloc_store(store, instance, data.len() as u32)?;
+        let size = VmMemory::mem_alloc_size(data.len(), codec)?;
+        let ptr = VmMemory::mem_alloc_fn_call(store, instance, alloc_fn, size)?;
 
         // load - memory
         let memory = instance
@@ -36,16 +57,27 @@ impl VmMemory {
 
         // load - memory view
         let memory_view = memory.view(store);
-        VmMemory::mem_write(memory_view, ptr, data)
+        VmMemory::mem_write_codec(memory_view, ptr, data, codec)
     }
 
     pub fn mem_write_mut_store(
         store: &mut StoreMut,
         instance: &Instance,
         val: &[u8],
+    ) -> Result<u32, EmMemError> {
+        VmMemory::mem_write_mut_store_with(store, instance, val, &Memory, DEF_MEM_ALLOC_FN)
+    }
+
+    pub fn mem_write_mut_store_with(
+        store: &mut StoreMut,
+        instance: &Instance,
+        val: &[u8],
+        codec: &dyn MemCodec,
+        alloc_fn: &str,
     ) -> Result<u32, EmMemError> {
         // alloc - memory
-        let ptr = VmMemory::mem_alloc_store_mut(store, instance, (val.len() as u32) + 4)?;
+        let size = VmMemory::mem_alloc_size(val.len(), codec)?;
+        let ptr = VmMemory::mem_alloc_fn_call(store, instance, alloc_fn, size)?;
 
         // load - memory
         let memory = instance
@@ -55,13 +87,22 @@ impl VmMemory {
 
         // load - memory view
         let memory_view = memory.view(store);
-        VmMemory::mem_write(memory_view, ptr, val)
+        VmMemory::mem_write_codec(memory_view, ptr, val, codec)
     }
 
     pub fn mem_read_store(
         store: &mut Store,
         instance: &Instance,
         ptr: u32,
+    ) -> Result<Vec<u8>, EmMemError> {
+        VmMemory::mem_read_store_codec(store, instance, ptr, &Memory)
+    }
+
+    pub fn mem_read_store_codec(
+        store: &mut Store,
+        instance: &Instance,
+        ptr: u32,
+        codec: &dyn MemCodec,
     ) -> Result<Vec<u8>, EmMemError> {
         let memory = instance
             .exports
@@ -69,12 +110,103 @@ impl VmMemory {
             .map_err(|e| EmMemError::MemoryReadGetMemoryFail(e.to_string()))?;
 
         let memory_view = memory.view(store);
-        VmMemory::mem_read(&memory_view, ptr)
+        VmMemory::mem_read_codec(&memory_view, ptr, codec)
+    }
+
+    pub fn mem_write_raw_store(
+        store: &mut Store,
+        instance: &Instance,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<(), EmMemError> {
+        // load - memory
+        let memory = instance
+            .exports
+            .get_memory("memory")
+            .map_err(|e| EmMemError::MemoryWriteLoadFail(e.to_string()))?;
+
+        // check - bounds ( raw writes never grow memory )
+        let memory_view = memory.view(store);
+        let mem_size = memory_view.data_size();
+        let end = offset.checked_add(data.len() as u64);
+        if end.is_none_or(|end| end > mem_size) {
+            return Err(EmMemError::MemoryWriteOutOfBounds(format!(
+                "offset {} + len {} exceeds memory size {}",
+                offset,
+                data.len(),
+                mem_size
+            )));
+        }
+
+        // write - memory ( no length prefix )
+        memory_view
+            .write(offset, data)
+            .map_err(|e| EmMemError::MemoryWriteFail(e.to_string()))
+    }
+
+    pub fn mem_snapshot_store(
+        store: &mut Store,
+        instance: &Instance,
+    ) -> Result<Vec<u8>, EmMemError> {
+        // load - memory
+        let memory = instance
+            .exports
+            .get_memory("memory")
+            .map_err(|e| EmMemError::MemoryReadGetMemoryFail(e.to_string()))?;
+
+        // read - memory ( whole linear memory )
+        let memory_view = memory.view(store);
+        memory_view
+            .copy_to_vec()
+            .map_err(|e| EmMemError::MemoryReadDataFail(e.to_string()))
+    }
+
+    pub fn mem_restore_store(
+        store: &mut Store,
+        instance: &Instance,
+        snapshot: &[u8],
+    ) -> Result<(), EmMemError> {
+        // load - memory
+        let memory = instance
+            .exports
+            .get_memory("memory")
+            .map_err(|e| EmMemError::MemoryWriteLoadFail(e.to_string()))?;
+
+        // grow - memory ( snapshot may be larger than a fresh instance )
+        memory
+            .grow_at_least(store, snapshot.len() as u64)
+            .map_err(|e| EmMemError::MemoryGrowFail(e.to_string()))?;
+
+        // write - memory ( from offset 0 )
+        let memory_view = memory.view(store);
+        memory_view
+            .write(0, snapshot)
+            .map_err(|e| EmMemError::MemoryWriteFail(e.to_string()))
+    }
+
+    pub fn mem_size(store: &Store, instance: &Instance) -> Result<u64, EmMemError> {
+        // load - memory
+        let memory = instance
+            .exports
+            .get_memory("memory")
+            .map_err(|e| EmMemError::MemoryReadGetMemoryFail(e.to_string()))?;
+
+        // load - memory size ( bytes )
+        Ok(memory.view(store).data_size())
     }
 
     pub fn mem_write(memory_view: MemoryView, ptr: u32, data: &[u8]) -> Result<u32, EmMemError> {
-        // encode - data ( len (4byte)  + data )
-        let buffer = Memory::encode(data);
+        VmMemory::mem_write_codec(memory_view, ptr, data, &Memory)
+    }
+
+    pub fn mem_write_codec(
+        memory_view: MemoryView,
+        ptr: u32,
+        data: &[u8],
+        codec: &dyn MemCodec,
+    ) -> Result<u32, EmMemError> {
+        // encode - data ( len ( header ) + data )
+        let buffer = codec.encode(data);
 
         // write - memory
         memory_view
@@ -85,20 +217,40 @@ impl VmMemory {
     }
 
     pub fn mem_read(mem_view: &MemoryView, ptr: u32) -> Result<Vec<u8>, EmMemError> {
+        VmMemory::mem_read_codec(mem_view, ptr, &Memory)
+    }
+
+    pub fn mem_read_codec(
+        mem_view: &MemoryView,
+        ptr: u32,
+        codec: &dyn MemCodec,
+    ) -> Result<Vec<u8>, EmMemError> {
         // read - memory ( data len )
-        let mut buffer = vec![0; 4];
+        let header_size = codec.header_size();
+        let mut buffer = vec![0; header_size];
         mem_view
             .read(ptr as u64, &mut buffer)
             .map_err(|e| EmMemError::MemoryReadDataLenFail(e.to_string()))?;
 
-        let len = Memory::decode_len(&buffer);
+        let len = codec.decode_len(&buffer);
+
+        // check - data within memory ( before allocating an untrusted len )
+        let end = (ptr as u64)
+            .saturating_add(header_size as u64)
+            .saturating_add(len as u64);
+        if end > mem_view.data_size() {
+            return Err(EmMemError::MemoryReadDataFail(format!(
+                "data out of bounds: ptr {} len {}",
+                ptr, len
+            )));
+        }
 
         // init - buffer
         let mut buffer = vec![0; len];
 
         // read - memory ( data )
         mem_view
-            .read((ptr as u64) + 4, &mut buffer)
+            .read((ptr as u64) + header_size as u64, &mut buffer)
             .map_err(|e| EmMemError::MemoryReadDataFail(e.to_string()))?;
 
         Ok(buffer)
@@ -109,32 +261,39 @@ impl VmMemory {
         instance: &Instance,
         size: u32,
     ) -> Result<u32, EmMemError> {
-        // load - function
-        let mem_alloc_fn = instance
-            .exports
-            .get_function("mem_alloc")
-            .map_err(|e| EmMemError::MemoryAllocGetFnFail(e.to_string()))?;
-
-        // call - function
-        let fn_result = mem_alloc_fn
-            .call(store, &[size.into()])
-            .map_err(|e| EmMemError::MemoryAllocCallFnFail(e.to_string()))?;
-
-        // load - ptr
-        let ptr = fn_result[0].i32().ok_or(EmMemError::MemoryAllocPtrEmpty)?;
-        Ok(ptr as u32)
+        VmMemory::mem_alloc_fn_call(store, instance, DEF_MEM_ALLOC_FN, size)
     }
 
     pub fn mem_alloc_store_mut(
         store: &mut StoreMut,
         instance: &Instance,
         size: u32,
+    ) -> Result<u32, EmMemError> {
+        VmMemory::mem_alloc_fn_call(store, instance, DEF_MEM_ALLOC_FN, size)
+    }
+
+    // encoded size ( data + codec header ), past u32 is an error rather than a truncated size
+    pub fn mem_alloc_size(data_len: usize, codec: &dyn MemCodec) -> Result<u32, EmMemError> {
+        data_len
+            .checked_add(codec.header_size())
+            .and_then(|size| u32::try_from(size).ok())
+            .ok_or_else(|| {
+                EmMemError::MemoryAllocSizeOverflow(format!(
+                    "{} + {} header bytes",
+                    data_len,
+                    codec.header_size()
+                ))
+            })
+    }
+
+    pub fn mem_alloc_fn_call(
+        store: &mut impl AsStoreMut,
+        instance: &Instance,
+        alloc_fn: &str,
+        size: u32,
     ) -> Result<u32, EmMemError> {
         // load - function
-        let mem_alloc_fn = instance
-            .exports
-            .get_function("mem_alloc")
-            .map_err(|e| EmMemError::MemoryAllocGetFnFail(e.to_string()))?;
+        let mem_alloc_fn = VmMemory::mem_alloc_fn_get(instance, alloc_fn)?;
 
         // call - function
         let fn_result = mem_alloc_fn
@@ -145,4 +304,93 @@ impl VmMemory {
         let ptr = fn_result[0].i32().ok_or(EmMemError::MemoryAllocPtrEmpty)?;
         Ok(ptr as u32)
     }
+
+    // free - guest buffer, `mem_free(ptr)` or `mem_free(ptr, size)` ( size as passed to the
+    // allocator ), Ok(false) when the guest exports no "mem_free"
+    pub fn mem_dealloc_store(
+        store: &mut Store,
+        instance: &Instance,
+        ptr: u32,
+        size: u32,
+    ) -> Result<bool, EmMemError> {
+        // load - function ( optional )
+        let Ok(mem_free_fn) = instance.exports.get_function(DEF_MEM_FREE_FN) else {
+            return Ok(false);
+        };
+
+        // check - signature ( a mismatch is an error, not a trap )
+        let fn_type = mem_free_fn.ty(store);
+        let args: Vec<Value> = match fn_type.params() {
+            [Type::I32] => vec![ptr.into()],
+            [Type::I32, Type::I32] => vec![ptr.into(), size.into()],
+            _ => return Err(EmMemError::MemoryFreeSignatureInvalid(fn_type.to_string())),
+        };
+
+        // call - function
+        mem_free_fn
+            .call(store, &args)
+            .map_err(|e| EmMemError::MemoryFreeCallFnFail(e.to_string()))?;
+
+        Ok(true)
+    }
+
+    // write - data to a guest buffer that is freed once `f` returns ( or unwinds )
+    pub fn with_guest_buffer<R>(
+        store: &mut Store,
+        instance: &Instance,
+        data: &[u8],
+        f: impl FnOnce(&mut Store, u32) -> R,
+    ) -> Result<R, EmMemError> {
+        VmMemory::with_guest_buffer_with(store, instance, data, &Memory, DEF_MEM_ALLOC_FN, f)
+    }
+
+    pub fn with_guest_buffer_with<R>(
+        store: &mut Store,
+        instance: &Instance,
+        data: &[u8],
+        codec: &dyn MemCodec,
+        alloc_fn: &str,
+        f: impl FnOnce(&mut Store, u32) -> R,
+    ) -> Result<R, EmMemError> {
+        let size = VmMemory::mem_alloc_size(data.len(), codec)?;
+        let ptr = VmMemory::mem_write_store_with(store, instance, data, codec, alloc_fn)?;
+        let guard = GuestBuffer {
+            store,
+            instance,
+            ptr,
+            size,
+        };
+
+        Ok(f(&mut *guard.store, guard.ptr))
+    }
+
+    fn mem_alloc_fn_get<'a>(
+        instance: &'a Instance,
+        alloc_fn: &str,
+    ) -> Result<&'a Function, EmMemError> {
+        instance.exports.get_function(alloc_fn).map_err(|e| {
+            // list - available fn exports
+            let fn_names: Vec<&String> = instance
+                .exports
+                .iter()
+                .functions()
+                .map(|(name, _)| name)
+                .collect();
+            EmMemError::MemoryAllocGetFnFail(format!("{} ( available fns : {:?} )", e, fn_names))
+        })
+    }
+}
+
+struct GuestBuffer<'a> {
+    store: &'a mut Store,
+    instance: &'a Instance,
+    ptr: u32,
+    size: u32,
+}
+
+impl Drop for GuestBuffer<'_> {
+    fn drop(&mut self) {
+        // free errors can't surface from drop, the buffer is leaked instead
+        let _ = VmMemory::mem_dealloc_store(self.store, self.instance, self.ptr, self.size);
+    }
 }
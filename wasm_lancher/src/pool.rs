@@ -0,0 +1,126 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+
+use crate::{EmVmError, VMLauncher};
+
+pub type LauncherFactory<T> =
+    Box<dyn Fn() -> Result<VMLauncher<T>, EmVmError> + Send + Sync + 'static>;
+
+struct PoolState<T: Send + Sync + Clone + 'static> {
+    idle: Vec<VMLauncher<T>>,
+    live: usize,
+}
+
+pub struct LauncherPool<T: Send + Sync + Clone + 'static> {
+    factory: LauncherFactory<T>,
+    max_live: usize,
+    state: Mutex<PoolState<T>>,
+    available: Condvar,
+}
+
+impl<T: Send + Sync + Clone + 'static> LauncherPool<T> {
+    // `factory` should reuse a compiled module ( `from_compiled` / `get_module_opcode` )
+    pub fn new(max_live: usize, factory: LauncherFactory<T>) -> Self {
+        LauncherPool {
+            factory,
+            max_live: max_live.max(1),
+            state: Mutex::new(PoolState {
+                idle: Vec::new(),
+                live: 0,
+            }),
+            available: Condvar::new(),
+        }
+    }
+
+    // blocks while `max_live` launchers are checked out
+    pub fn checkout(&self) -> Result<PooledLauncher<'_, T>, EmVmError> {
+        let mut state = self.state();
+        loop {
+            // load - idle launcher
+            if let Some(launcher) = state.idle.pop() {
+                return Ok(PooledLauncher {
+                    pool: self,
+                    launcher: Some(launcher),
+                });
+            }
+
+            // new - launcher ( outside the lock )
+            if state.live < self.max_live {
+                state.live += 1;
+                drop(state);
+
+                return match (self.factory)() {
+                    Ok(launcher) => Ok(PooledLauncher {
+                        pool: self,
+                        launcher: Some(launcher),
+                    }),
+                    Err(e) => {
+                        self.release_slot();
+                        Err(e)
+                    }
+                };
+            }
+
+            state = self
+                .available
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    pub fn live_count(&self) -> usize {
+        self.state().live
+    }
+
+    pub fn idle_count(&self) -> usize {
+        self.state().idle.len()
+    }
+
+    fn checkin(&self, mut launcher: VMLauncher<T>) {
+        // reset - fresh store & instance, a launcher failing to reset is dropped
+        if launcher.reset().is_err() {
+            self.release_slot();
+            return;
+        }
+
+        self.state().idle.push(launcher);
+        self.available.notify_one();
+    }
+
+    fn release_slot(&self) {
+        self.state().live -= 1;
+        self.available.notify_one();
+    }
+
+    // lock - state, a poisoned lock is still used ( checkin runs inside `Drop` )
+    fn state(&self) -> MutexGuard<'_, PoolState<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+pub struct PooledLauncher<'a, T: Send + Sync + Clone + 'static> {
+    pool: &'a LauncherPool<T>,
+    launcher: Option<VMLauncher<T>>,
+}
+
+impl<T: Send + Sync + Clone + 'static> Deref for PooledLauncher<'_, T> {
+    type Target = VMLauncher<T>;
+
+    fn deref(&self) -> &Self::Target {
+        self.launcher.as_ref().unwrap()
+    }
+}
+
+impl<T: Send + Sync + Clone + 'static> DerefMut for PooledLauncher<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.launcher.as_mut().unwrap()
+    }
+}
+
+impl<T: Send + Sync + Clone + 'static> Drop for PooledLauncher<'_, T> {
+    fn drop(&mut self) {
+        if let Some(launcher) = self.launcher.take() {
+            self.pool.checkin(launcher);
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+#[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize, Clone)]
+pub enum TypesError {
+    DecStrEmpty,
+    DecStrInvalidChar(String),
+    DecStrOverflow(String),
+}
+
+// 256bit unsigned integer, 4 x u64 limbs ( least significant first )
+// borsh : 32 bytes, little endian
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, BorshSerialize, BorshDeserialize)]
+pub struct U256([u64; 4]);
+
+impl U256 {
+    pub const ZERO: U256 = U256([0; 4]);
+    pub const ONE: U256 = U256([1, 0, 0, 0]);
+    pub const MAX: U256 = U256([u64::MAX; 4]);
+
+    // 10^19, the largest power of 10 that fits in a u64
+    const DEC_CHUNK: u64 = 10_000_000_000_000_000_000;
+    const DEC_CHUNK_DIGITS: usize = 19;
+
+    pub fn from_limbs(limbs: [u64; 4]) -> Self {
+        U256(limbs)
+    }
+
+    pub fn limbs(&self) -> [u64; 4] {
+        self.0
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0; 4]
+    }
+
+    pub fn from_dec_str(dec: &str) -> Result<Self, TypesError> {
+        if dec.is_empty() {
+            return Err(TypesError::DecStrEmpty);
+        }
+
+        // parse - value = value * 10 + digit
+        let mut value = U256::ZERO;
+        for c in dec.chars() {
+            let digit = c
+                .to_digit(10)
+                .ok_or_else(|| TypesError::DecStrInvalidChar(c.to_string()))?;
+            value = value
+                .mul_u64(10)
+                .and_then(|value| value.checked_add(U256::from(digit as u64)))
+                .ok_or_else(|| TypesError::DecStrOverflow(dec.to_string()))?;
+        }
+
+        Ok(value)
+    }
+
+    pub fn to_dec_str(&self) -> String {
+        if self.is_zero() {
+            return "0".to_string();
+        }
+
+        // split - 19 digit chunks ( least significant first )
+        let mut chunks = Vec::new();
+        let mut value = *self;
+        while !value.is_zero() {
+            let (quotient, remainder) = value.div_rem_u64(Self::DEC_CHUNK);
+            chunks.push(remainder);
+            value = quotient;
+        }
+
+        // join - chunks, zero padded except the most significant
+        let mut dec = chunks.pop().unwrap().to_string();
+        for chunk in chunks.iter().rev() {
+            dec.push_str(&format!(
+                "{:0width$}",
+                chunk,
+                width = Self::DEC_CHUNK_DIGITS
+            ));
+        }
+
+        dec
+    }
+
+    pub fn checked_add(&self, rhs: U256) -> Option<U256> {
+        let mut result = [0u64; 4];
+        let mut carry = false;
+        for (i, limb) in result.iter_mut().enumerate() {
+            let (sum, overflow_a) = self.0[i].overflowing_add(rhs.0[i]);
+            let (sum, overflow_b) = sum.overflowing_add(carry as u64);
+            *limb = sum;
+            carry = overflow_a || overflow_b;
+        }
+
+        match carry {
+            true => None,
+            false => Some(U256(result)),
+        }
+    }
+
+    pub fn checked_sub(&self, rhs: U256) -> Option<U256> {
+        let mut result = [0u64; 4];
+        let mut borrow = false;
+        for (i, limb) in result.iter_mut().enumerate() {
+            let (diff, overflow_a) = self.0[i].overflowing_sub(rhs.0[i]);
+            let (diff, overflow_b) = diff.overflowing_sub(borrow as u64);
+            *limb = diff;
+            borrow = overflow_a || overflow_b;
+        }
+
+        match borrow {
+            true => None,
+            false => Some(U256(result)),
+        }
+    }
+
+    pub fn checked_mul(&self, rhs: U256) -> Option<U256> {
+        // mul - schoolbook, 8 limb product
+        let mut product = [0u64; 8];
+        for i in 0..4 {
+            let mut carry: u128 = 0;
+            for j in 0..4 {
+                let cur = product[i + j] as u128 + (self.0[i] as u128) * (rhs.0[j] as u128) + carry;
+                product[i + j] = cur as u64;
+                carry = cur >> 64;
+            }
+            product[i + 4] = carry as u64;
+        }
+
+        // check - overflow ( high limbs must be empty )
+        if product[4..].iter().any(|limb| *limb != 0) {
+            return None;
+        }
+
+        Some(U256([product[0], product[1], product[2], product[3]]))
+    }
+
+    fn mul_u64(&self, rhs: u64) -> Option<U256> {
+        self.checked_mul(U256::from(rhs))
+    }
+
+    fn div_rem_u64(&self, rhs: u64) -> (U256, u64) {
+        let mut quotient = [0u64; 4];
+        let mut remainder: u128 = 0;
+        for i in (0..4).rev() {
+            let cur = (remainder << 64) | self.0[i] as u128;
+            quotient[i] = (cur / rhs as u128) as u64;
+            remainder = cur % rhs as u128;
+        }
+
+        (U256(quotient), remainder as u64)
+    }
+}
+
+impl From<u64> for U256 {
+    fn from(value: u64) -> Self {
+        U256([value, 0, 0, 0])
+    }
+}
+
+impl From<u128> for U256 {
+    fn from(value: u128) -> Self {
+        U256([value as u64, (value >> 64) as u64, 0, 0])
+    }
+}
+
+impl Ord for U256 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // compare - most significant limb first
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_dec_str())
+    }
+}
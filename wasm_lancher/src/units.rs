@@ -0,0 +1,75 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::types::*;
+
+pub const BTC_DECIMALS: usize = 8;
+pub const ETH_DECIMALS: usize = 18;
+pub const TRX_DECIMALS: usize = 6;
+
+#[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize, Clone)]
+pub enum UnitsError {
+    AmountInvalid(String),
+    AmountTooPrecise { decimals: usize, got: usize },
+    AmountParseFail(TypesError),
+}
+
+pub fn btc_to_sat(btc: &str) -> Result<U256, UnitsError> {
+    parse_units(btc, BTC_DECIMALS)
+}
+
+pub fn sat_to_btc(sat: U256) -> String {
+    format_units(sat, BTC_DECIMALS)
+}
+
+pub fn eth_to_wei(eth: &str) -> Result<U256, UnitsError> {
+    parse_units(eth, ETH_DECIMALS)
+}
+
+pub fn wei_to_eth(wei: U256) -> String {
+    format_units(wei, ETH_DECIMALS)
+}
+
+pub fn trx_to_sun(trx: &str) -> Result<U256, UnitsError> {
+    parse_units(trx, TRX_DECIMALS)
+}
+
+pub fn sun_to_trx(sun: U256) -> String {
+    format_units(sun, TRX_DECIMALS)
+}
+
+// "1.5" with 8 decimals -> 150000000, more than `decimals` fraction digits is rejected
+pub fn parse_units(amount: &str, decimals: usize) -> Result<U256, UnitsError> {
+    // split - integer & fraction ( "1", "1.5", not "1." / ".5" )
+    let (integer, fraction) = match amount.split_once('.') {
+        Some((integer, fraction)) => (integer, fraction),
+        None => (amount, ""),
+    };
+    if integer.is_empty() || (amount.contains('.') && fraction.is_empty()) {
+        return Err(UnitsError::AmountInvalid(amount.to_string()));
+    }
+
+    // check - precision
+    if fraction.len() > decimals {
+        return Err(UnitsError::AmountTooPrecise {
+            decimals,
+            got: fraction.len(),
+        });
+    }
+
+    // parse - integer digits followed by the zero padded fraction
+    let dec = format!("{}{:0<width$}", integer, fraction, width = decimals);
+    U256::from_dec_str(&dec).map_err(UnitsError::AmountParseFail)
+}
+
+// 150000000 with 8 decimals -> "1.5", trailing fraction zeros trimmed
+pub fn format_units(value: U256, decimals: usize) -> String {
+    // pad - at least one integer digit
+    let dec = format!("{:0>width$}", value.to_dec_str(), width = decimals + 1);
+    let (integer, fraction) = dec.split_at(dec.len() - decimals);
+
+    let fraction = fraction.trim_end_matches('0');
+    match fraction.is_empty() {
+        true => integer.to_string(),
+        false => format!("{}.{}", integer, fraction),
+    }
+}